@@ -1,17 +1,402 @@
 extern crate clap;
 
-use clap::{arg, Command};
+use clap::{arg, Arg, ArgMatches, Command};
 use itertools::Itertools;
-use phylo::prelude::RootedTree;
-use phylo::tree::io::Newick;
-use phylo::tree::simple_rtree::RootedMetaTree;
-use phylo::tree::{PhyloTree, SimpleRootedTree};
+use phylo::prelude::{RootedMetaTree, RootedTree};
+use phylo::tree::PhyloTree;
 use std::cmp;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
-use PD::pd::phylogenetic_diversity::{binarize_tree, TreePDMap};
+use std::time::Duration;
+use PD::pd::phylogenetic_diversity::{add_sampled_ancestor_tips, from_annotated_newick, random_resolutions, resolve_polytomies, MissingLengthPolicy, NodeWeightPolicy, PDOptions, ResolveStrategy, RootedPhylogeneticDiversity, TieBreak, TreePDMap, ROBUST_EXACT_MAX_REMOVALS};
+use PD::pd::conflicts::ConflictGraph;
+use PD::pd::dependencies::DependencyNetwork;
+use PD::pd::groups::{GroupQuota, Groups};
+use PD::pd::noahs_ark::{solve_noahs_ark, Project};
+use PD::pd::normalization::NormalizationKind;
+use PD::pd::null_model::{get_null_normPD, get_null_normPD_profile, NullDistribution};
+use PD::pd::reserves::Sites;
 use PD::pd::TreePD;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+
+/// Adds the arguments shared by all subcommands that read a tree
+fn tree_args(command: Command) -> Command {
+    command
+        .arg(
+            arg!(-f --file <TREE_FILE> "Input Tree File")
+                .required(true)
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            arg!(--missing_lengths <POLICY> "Treatment of edges without a branch length")
+                .value_parser(["error", "zero", "unit", "mean"])
+                .default_value("zero"),
+        )
+        .arg(
+            arg!(--tie_break <RULE> "Rule used to choose between optimal sets of equal value")
+                .value_parser(["lexicographic", "fewest_edges", "most_edges"])
+                .default_value("lexicographic"),
+        )
+        .arg(
+            arg!(--resolve <STRATEGY> "Resolution of polytomies (average reports values averaged over random resolutions, and sets of the first)")
+                .value_parser(["caterpillar", "balanced", "random", "average"])
+                .default_value("caterpillar"),
+        )
+        .arg(
+            arg!(--node_weights <POLICY> "Use of node weights annotated in Newick comments as bonuses or costs")
+                .value_parser(["ignore", "bonus", "cost"])
+                .default_value("ignore"),
+        )
+        .arg(
+            arg!(--node_weight_key <KEY> "Key of the node weight in Newick comments such as [&weight=2]; without it, bare numeric comments such as [2] are read"),
+        )
+        .arg(
+            arg!(--sampled_ancestors "Treat labelled internal nodes as selectable taxa")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--seed <SEED> "Seed for random resolutions")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("0"),
+        )
+        .arg(
+            arg!(--replicates <REPLICATES> "Number of random resolutions to average over")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("100"),
+        )
+}
+
+fn num_taxa_arg() -> Arg {
+    arg!(-n --num_taxa <NUM_TAXA> "Number of taxa")
+        .required(true)
+        .value_parser(clap::value_parser!(usize))
+}
+
+fn norm_arg() -> Arg {
+    arg!(--norm <NORM> "Normalization of PD: per edge, per taxon, by total tree length, by PD of the MRCA clade or per unit of crown age")
+        .value_parser(["edges", "taxa", "tree_length", "mrca_clade", "crown_age"])
+        .default_value("edges")
+}
+
+/// Reads the first tree in the input file and resolves its polytomies. More than
+/// one tree is returned only when averaging over random resolutions.
+fn read_trees(args: &ArgMatches) -> Result<Vec<PhyloTree>> {
+    let mut tree_file = File::open(args.get_one::<String>("file").expect("required"))?;
+    let mut trees = String::new();
+
+    tree_file.read_to_string(&mut trees)?;
+    let tree_string = trees.split('\n').collect_vec()[0];
+    let mut tree = from_annotated_newick(
+        tree_string.as_bytes(),
+        args.get_one::<String>("node_weight_key").map(String::as_str),
+    )?;
+    if args.get_flag("sampled_ancestors") {
+        add_sampled_ancestor_tips(&mut tree);
+    }
+    if tree.is_binary() {
+        return Ok(vec![tree]);
+    }
+    let seed = *args.get_one::<u64>("seed").expect("default");
+    let strategy = match args.get_one::<String>("resolve").expect("default").as_str() {
+        "average" => {
+            let replicates = *args.get_one::<usize>("replicates").expect("default");
+            return Ok(random_resolutions(&tree, cmp::max(replicates, 1), seed));
+        }
+        "balanced" => ResolveStrategy::Balanced,
+        "random" => ResolveStrategy::Random(seed),
+        _ => ResolveStrategy::Caterpillar,
+    };
+    resolve_polytomies(&mut tree, strategy);
+    Ok(vec![tree])
+}
+
+/// Reads taxon labels from a file, one per line
+fn read_taxa(path: &str) -> Result<Vec<String>> {
+    let mut taxa_file = File::open(path)?;
+    let mut taxa = String::new();
+
+    taxa_file.read_to_string(&mut taxa)?;
+    Ok(taxa
+        .lines()
+        .map(|taxon| taxon.trim().to_string())
+        .filter(|taxon| !taxon.is_empty())
+        .collect())
+}
+
+/// Reads taxon values from a CSV file of `taxon,value` rows with an optional header
+fn read_values(path: &str) -> Result<HashMap<String, f32>> {
+    let mut values_file = File::open(path)?;
+    let mut values = String::new();
+
+    values_file.read_to_string(&mut values)?;
+    let mut taxon_values = HashMap::new();
+    for (line_num, line) in values.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let (taxon, value) = line.split_once(',').ok_or_else(|| anyhow!("line {}: expected taxon,value", line_num + 1))?;
+        match value.trim().parse::<f32>() {
+            Ok(value) => {
+                taxon_values.insert(taxon.trim().to_string(), value);
+            }
+            Err(_) if line_num == 0 => continue,
+            Err(err) => bail!("line {}: invalid value {:?}: {}", line_num + 1, value.trim(), err),
+        }
+    }
+    Ok(taxon_values)
+}
+
+/// Reads conservation projects from a CSV file of `taxon,cost,probability` rows with an
+/// optional header
+fn read_projects(path: &str) -> Result<Vec<(String, Project<f32>)>> {
+    let mut projects_file = File::open(path)?;
+    let mut projects = String::new();
+
+    projects_file.read_to_string(&mut projects)?;
+    let mut taxon_projects = vec![];
+    for (line_num, line) in projects.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let (taxon, cost, probability) = line
+            .split(',')
+            .map(str::trim)
+            .collect_tuple()
+            .ok_or_else(|| anyhow!("line {}: expected taxon,cost,probability", line_num + 1))?;
+        let project = match (cost.parse::<usize>(), probability.parse::<f32>()) {
+            (Ok(cost), Ok(survival)) => Project { cost, survival },
+            _ if line_num == 0 => continue,
+            _ => bail!("line {}: invalid cost or probability", line_num + 1),
+        };
+        taxon_projects.push((taxon.to_string(), project));
+    }
+    Ok(taxon_projects)
+}
+
+/// Reads a site by taxon presence matrix from a CSV file whose header row names the
+/// taxa, with an optional `cost` column after the site names. Returns the name, the
+/// taxa present and the cost (1 if not given) of every site.
+fn read_sites(path: &str) -> Result<Vec<(String, Vec<String>, f32)>> {
+    let mut sites_file = File::open(path)?;
+    let mut sites = String::new();
+
+    sites_file.read_to_string(&mut sites)?;
+    let mut lines = sites.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or_else(|| anyhow!("empty presence matrix"))?.split(',').map(str::trim).collect_vec();
+    let has_cost = header.get(1) == Some(&"cost");
+    let taxa = &header[if has_cost { 2 } else { 1 }..];
+    lines
+        .map(|line| {
+            let fields = line.split(',').map(str::trim).collect_vec();
+            if fields.len() != header.len() {
+                bail!("site {}: expected {} columns", fields[0], header.len());
+            }
+            let cost = if has_cost { fields[1].parse::<f32>()? } else { 1.0 };
+            let present = fields[header.len() - taxa.len()..]
+                .iter()
+                .zip(taxa)
+                .filter(|(x, _)| x.parse::<f32>().is_ok_and(|x| x != 0.0))
+                .map(|(_, taxon)| taxon.to_string())
+                .collect();
+            Ok((fields[0].to_string(), present, cost))
+        })
+        .collect()
+}
+
+/// Reads group assignments from a CSV file of `taxon,group` rows, with an optional
+/// `taxon,group` header, and quotas from a CSV file of `group,min,max` rows with an
+/// optional header, where an empty bound is unbounded. Groups without a quota in the
+/// file get `default_quota`.
+fn read_groups(path: &str, quotas_path: Option<&String>, default_quota: GroupQuota) -> Result<Vec<(Vec<String>, GroupQuota)>> {
+    let mut groups_file = File::open(path)?;
+    let mut groups = String::new();
+
+    groups_file.read_to_string(&mut groups)?;
+    let mut group_names: Vec<String> = vec![];
+    let mut group_taxa: Vec<(Vec<String>, GroupQuota)> = vec![];
+    for (line_num, line) in groups.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let (taxon, group) = line
+            .split(',')
+            .map(str::trim)
+            .collect_tuple()
+            .ok_or_else(|| anyhow!("line {}: expected taxon,group", line_num + 1))?;
+        if line_num == 0 && (taxon, group) == ("taxon", "group") {
+            continue;
+        }
+        match group_names.iter().position(|x| x == group) {
+            Some(index) => group_taxa[index].0.push(taxon.to_string()),
+            None => {
+                group_names.push(group.to_string());
+                group_taxa.push((vec![taxon.to_string()], default_quota));
+            }
+        }
+    }
+    let Some(quotas_path) = quotas_path else {
+        return Ok(group_taxa);
+    };
+    let mut quotas_file = File::open(quotas_path)?;
+    let mut quotas = String::new();
+
+    quotas_file.read_to_string(&mut quotas)?;
+    for (line_num, line) in quotas.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let (group, min, max) = line
+            .split(',')
+            .map(str::trim)
+            .collect_tuple()
+            .ok_or_else(|| anyhow!("line {}: expected group,min,max", line_num + 1))?;
+        let parse_bound = |bound: &str, unbounded: usize| match bound {
+            "" => Ok(unbounded),
+            _ => bound.parse::<usize>(),
+        };
+        let quota = match (parse_bound(min, 0), parse_bound(max, usize::MAX)) {
+            (Ok(min), Ok(max)) => GroupQuota { min, max },
+            _ if line_num == 0 => continue,
+            _ => bail!("line {}: invalid quota", line_num + 1),
+        };
+        let index = group_names
+            .iter()
+            .position(|x| x == group)
+            .ok_or_else(|| anyhow!("line {}: unknown group {}", line_num + 1, group))?;
+        group_taxa[index].1 = quota;
+    }
+    Ok(group_taxa)
+}
+
+/// Reads clades from a file listing, on each line, comma-separated taxa whose most
+/// recent common ancestor is the root of the clade
+fn read_clades(path: &str) -> Result<Vec<Vec<String>>> {
+    let mut clades_file = File::open(path)?;
+    let mut clades = String::new();
+
+    clades_file.read_to_string(&mut clades)?;
+    Ok(clades
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split(',').map(|taxon| taxon.trim().to_string()).collect())
+        .collect())
+}
+
+/// Reads an edge list of pairs of taxa, such as `taxon,dependency` rows of a dependency
+/// network, separated by commas or whitespace
+fn read_edge_list(path: &str) -> Result<Vec<(String, String)>> {
+    let mut edges_file = File::open(path)?;
+    let mut edges = String::new();
+
+    edges_file.read_to_string(&mut edges)?;
+    edges
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_num, line)| {
+            line.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|x| !x.is_empty())
+                .map(str::to_string)
+                .collect_tuple()
+                .ok_or_else(|| anyhow!("line {}: expected a pair of taxa", line_num + 1))
+        })
+        .collect()
+}
+
+/// Builds PD tables for every tree, warning about edges that had no branch length
+fn build_tree_pds<'a>(trees: &'a [PhyloTree], args: &ArgMatches) -> Result<Vec<TreePD<'a, String, f32, f32>>> {
+    let policy = missing_length_policy(args)?;
+    let options = PDOptions {
+        missing_lengths: policy,
+        tie_break: args.get_one::<String>("tie_break").expect("default").parse::<TieBreak>()?,
+        node_weights: node_weight_policy(args)?,
+    };
+    let tree_pds = trees
+        .iter()
+        .map(|tree| TreePD::with_options(tree, options))
+        .collect::<Result<Vec<_>, _>>()?;
+    warn_missing_lengths(tree_pds[0].num_missing_lengths(), policy);
+    Ok(tree_pds)
+}
+
+fn node_weight_policy(args: &ArgMatches) -> Result<NodeWeightPolicy> {
+    Ok(args
+        .get_one::<String>("node_weights")
+        .expect("default")
+        .parse::<NodeWeightPolicy>()?)
+}
+
+/// Resolves edge lengths and applies node weights for commands that work on edge
+/// lengths directly, warning when weighted lengths are negative since these commands
+/// rely on maxPD sets being nested across the number of taxa
+fn weighted_edge_lengths(tree: &PhyloTree, args: &ArgMatches) -> Result<(Vec<f32>, usize)> {
+    let (mut edge_lengths, num_missing) = tree.resolve_edge_lengths(missing_length_policy(args)?)?;
+    tree.apply_node_weights(&mut edge_lengths, node_weight_policy(args)?);
+    if edge_lengths.iter().any(|x| *x < 0.0) {
+        eprintln!("warning: node weights make some edge lengths negative; results may not be optimal");
+    }
+    Ok((edge_lengths, num_missing))
+}
+
+fn missing_length_policy(args: &ArgMatches) -> Result<MissingLengthPolicy> {
+    Ok(args
+        .get_one::<String>("missing_lengths")
+        .expect("default")
+        .parse::<MissingLengthPolicy>()?)
+}
+
+fn warn_missing_lengths(num_missing: usize, policy: MissingLengthPolicy) {
+    if num_missing > 0 {
+        eprintln!(
+            "warning: {} edge(s) had no branch length and were treated as {}",
+            num_missing,
+            match policy {
+                MissingLengthPolicy::Unit => "unit length",
+                MissingLengthPolicy::Mean => "the mean branch length",
+                _ => "zero length",
+            }
+        );
+    }
+}
+
+/// Normalization of the null model reported by the avg commands, off by default
+fn null_norm_arg() -> Arg {
+    arg!(--norm <NORM> "Also report the mean and sd of PD under this normalization over random taxa sets")
+        .value_parser(["edges", "taxa", "tree_length", "mrca_clade", "crown_age"])
+}
+
+/// Number of random taxa sets drawn by the null model of the avg commands
+fn samples_arg() -> Arg {
+    arg!(--samples <SAMPLES> "Number of random taxa sets drawn when normalized PD moments are estimated by Monte Carlo")
+        .value_parser(clap::value_parser!(usize))
+        .default_value("10000")
+}
+
+/// Computes the null distributions of normalized PD of every tree with `null`, if a
+/// normalization was requested
+fn null_norms<D>(
+    tree_pds: &[TreePD<String, f32, f32>],
+    args: &ArgMatches,
+    null: impl Fn(&TreePD<String, f32, f32>, NormalizationKind, usize, u64) -> D,
+) -> Result<Option<Vec<D>>> {
+    let Some(norm) = args.get_one::<String>("norm") else {
+        return Ok(None);
+    };
+    let norm = norm.parse::<NormalizationKind>()?;
+    let samples = *args.get_one::<usize>("samples").expect("default");
+    let seed = *args.get_one::<u64>("seed").expect("default");
+    Ok(Some(tree_pds.iter().map(|tree_pd| null(tree_pd, norm, samples, seed)).collect()))
+}
+
+/// Reports the mean and standard deviation of normalized PD over random sets of a
+/// number of taxa, averaged over trees, warning when they are Monte Carlo estimates
+fn null_norm_report(nulls: &[Option<NullDistribution<f32>>], num_taxa: usize) -> String {
+    let Some(nulls) = nulls.iter().cloned().collect::<Option<Vec<_>>>() else {
+        return "\nexpected normalized PD: NA\nsd normalized PD: NA".to_string();
+    };
+    if let Some(null) = nulls.iter().find(|x| !x.exact) {
+        eprintln!("warning: normalized PD moments for k={} are estimated from {} random taxa sets", num_taxa, null.samples);
+    }
+    format!(
+        "\nexpected normalized PD: {}\nsd normalized PD: {}",
+        nulls.iter().map(|x| x.mean).sum::<f32>() / nulls.len() as f32,
+        nulls.iter().map(|x| x.sd).sum::<f32>() / nulls.len() as f32,
+    )
+}
+
+/// Mean of a statistic over the PD tables of all resolutions of a tree
+fn mean(tree_pds: &[TreePD<String, f32, f32>], statistic: impl Fn(&TreePD<String, f32, f32>) -> f32) -> f32 {
+    tree_pds.iter().map(statistic).sum::<f32>() / tree_pds.len() as f32
+}
 
 fn main() -> Result<()>{
     let matches = Command::new("Phylogenetics Rust")
@@ -21,73 +406,212 @@ fn main() -> Result<()>{
             Command::new("PD")
                 .about("Compute Phylogenetic Diversity")
                 .subcommand(
-                    Command::new("min")
-                        .about("Compute minPD")
+                    tree_args(Command::new("min").about("Compute minPD"))
+                        .arg(num_taxa_arg()),
+                )
+                .subcommand(
+                    tree_args(Command::new("max").about("Compute maxPD"))
+                        .arg(num_taxa_arg()),
+                )
+                .subcommand(
+                    tree_args(Command::new("all_max").about("Compute maxPD"))
+                        .arg(
+                            arg!(--backend <BACKEND> "Compute maxPD with the full PD tables, or from the greedy maxPD ranking (faster, reports maxPD values only)")
+                                .value_parser(["table", "greedy"])
+                                .default_value("table"),
+                        )
+                )
+                .subcommand(
+                    tree_args(Command::new("all_min").about("Compute maxPD"))
+                )
+                .subcommand(
+                    tree_args(Command::new("avg").about("Compute avgPD and the mean and sd of normalized PD over random taxa sets"))
+                        .arg(num_taxa_arg())
+                        .arg(null_norm_arg())
+                        .arg(samples_arg()),
+                )
+                .subcommand(
+                    tree_args(Command::new("all_avg").about("Compute all avgPD and the mean and sd of normalized PD over random taxa sets"))
+                        .arg(null_norm_arg())
+                        .arg(samples_arg()),
+                )
+                .subcommand(
+                    tree_args(Command::new("clades").about("Report PD statistics of every clade as CSV (of the first resolution when averaging)"))
+                        .arg(
+                            arg!(-n --num_taxa <NUM_TAXA> "Comma-separated numbers of taxa to report min/max/avg PD for")
+                                .value_parser(clap::value_parser!(usize))
+                                .value_delimiter(',')
+                                .default_value("3"),
+                        ),
+                )
+                .subcommand(
+                    tree_args(Command::new("complement").about("Compute the taxa adding the most PD to a protected set"))
                         .arg(
-                            arg!(-f --file <TREE_FILE> "Input Tree File")
+                            arg!(-t --taxa <TAXA_FILE> "File listing the protected taxa, one per line")
                                 .required(true)
                                 .value_parser(clap::value_parser!(String)),
                         )
+                        .arg(num_taxa_arg()),
+                )
+                .subcommand(
+                    tree_args(Command::new("robust").about("Compute the taxa set maximizing the PD remaining after the worst-case loss of some of its taxa"))
+                        .arg(num_taxa_arg())
                         .arg(
-                            arg!(-n --num_taxa <NUM_TAXA> "Input Tree File")
+                            arg!(-r --removals <REMOVALS> "Number of taxa lost")
                                 .required(true)
                                 .value_parser(clap::value_parser!(usize)),
                         ),
                 )
                 .subcommand(
-                    Command::new("max")
-                        .about("Compute maxPD")
+                    tree_args(Command::new("value").about("Report the taxa sets maximizing PD plus lambda times the total taxon value over a range of lambda, with the interval each stays optimal over, as CSV (of the first resolution when averaging)"))
                         .arg(
-                            arg!(-f --file <TREE_FILE> "Input Tree File")
+                            arg!(-v --values <VALUES_FILE> "CSV file of taxon,value rows; unlisted taxa have value 0")
                                 .required(true)
                                 .value_parser(clap::value_parser!(String)),
                         )
+                        .arg(num_taxa_arg())
+                        .arg(
+                            arg!(-l --lambda <LAMBDA> "Range of weights of taxon values against PD, as min,max")
+                                .value_parser(clap::value_parser!(f32))
+                                .value_delimiter(',')
+                                .num_args(2)
+                                .default_value("0,2"),
+                        ),
+                )
+                .subcommand(
+                    tree_args(Command::new("viable").about("Compute the taxa set maximizing PD among sets in which every taxon has one of its dependencies (e.g. prey) saved"))
                         .arg(
-                            arg!(-n --num_taxa <NUM_TAXA> "Input Tree File")
+                            arg!(-d --dependencies <EDGE_FILE> "Edge list of taxon,dependency rows")
                                 .required(true)
-                                .value_parser(clap::value_parser!(usize)),
+                                .value_parser(clap::value_parser!(String)),
+                        )
+                        .arg(num_taxa_arg())
+                        .arg(
+                            arg!(--max_nodes <MAX_NODES> "Number of search nodes explored before reporting the best set found so far")
+                                .value_parser(clap::value_parser!(usize))
+                                .default_value("100000"),
                         ),
                 )
                 .subcommand(
-                    Command::new("all_max")
-                        .about("Compute maxPD")
+                    tree_args(Command::new("ark").about("Fund at most one conservation project per taxon within a budget to maximize expected PD"))
                         .arg(
-                            arg!(-f --file <TREE_FILE> "Input Tree File")
+                            arg!(-p --projects <PROJECTS_FILE> "CSV file of taxon,cost,probability rows with integer costs")
                                 .required(true)
                                 .value_parser(clap::value_parser!(String)),
                         )
+                        .arg(
+                            arg!(-b --budget <BUDGET> "Integer budget")
+                                .required(true)
+                                .value_parser(clap::value_parser!(usize)),
+                        ),
                 )
                 .subcommand(
-                    Command::new("all_min")
-                        .about("Compute maxPD")
+                    tree_args(Command::new("reserves").about("Select sites maximizing the PD of the union of the taxa they protect"))
                         .arg(
-                            arg!(-f --file <TREE_FILE> "Input Tree File")
+                            arg!(-s --sites <SITES_FILE> "CSV site by taxon presence matrix, with an optional cost column")
                                 .required(true)
                                 .value_parser(clap::value_parser!(String)),
                         )
+                        .arg(
+                            arg!(-m --num_sites <NUM_SITES> "Number of sites to select")
+                                .value_parser(clap::value_parser!(usize))
+                                .conflicts_with("budget"),
+                        )
+                        .arg(
+                            arg!(-b --budget <BUDGET> "Budget for the total cost of the sites")
+                                .value_parser(clap::value_parser!(f32)),
+                        )
+                        .arg(
+                            arg!(--method <METHOD> "Greedy, greedy followed by local search, or exact branch-and-bound")
+                                .value_parser(["greedy", "local", "exact"])
+                                .default_value("local"),
+                        )
+                        .arg(
+                            arg!(--max_nodes <MAX_NODES> "Number of search nodes explored by the exact method before reporting the best selection found so far")
+                                .value_parser(clap::value_parser!(usize))
+                                .default_value("100000"),
+                        ),
                 )
                 .subcommand(
-                    Command::new("avg")
-                        .about("Compute avgPD")
+                    tree_args(Command::new("conflicts").about("Compute the taxa set with max or min PD among sets without conflicting taxa"))
                         .arg(
-                            arg!(-f --file <TREE_FILE> "Input Tree File")
+                            arg!(-c --conflicts <EDGE_FILE> "Edge list of pairs of taxa that cannot be selected together")
                                 .required(true)
                                 .value_parser(clap::value_parser!(String)),
                         )
+                        .arg(num_taxa_arg())
                         .arg(
-                            arg!(-n --num_taxa <NUM_TAXA> "Input Tree File")
+                            arg!(--objective <OBJECTIVE> "Maximize or minimize PD")
+                                .value_parser(["min", "max"])
+                                .default_value("max"),
+                        )
+                        .arg(
+                            arg!(--time_limit <SECONDS> "Time after which the best set found so far is reported")
+                                .value_parser(clap::value_parser!(f64))
+                                .default_value("10"),
+                        ),
+                )
+                .subcommand(
+                    tree_args(Command::new("quotas").about("Compute the taxa set with max or min PD among sets meeting quotas on the number of taxa per group"))
+                        .arg(
+                            arg!(-g --groups <GROUPS_FILE> "CSV file of taxon,group rows; a taxon may belong to several groups")
                                 .required(true)
+                                .value_parser(clap::value_parser!(String)),
+                        )
+                        .arg(
+                            arg!(-q --quotas <QUOTAS_FILE> "CSV file of group,min,max rows overriding the default quota")
+                                .value_parser(clap::value_parser!(String)),
+                        )
+                        .arg(
+                            arg!(--at_least <MIN> "Fewest taxa to select from every group")
+                                .value_parser(clap::value_parser!(usize))
+                                .default_value("0"),
+                        )
+                        .arg(
+                            arg!(--at_most <MAX> "Most taxa to select from every group")
                                 .value_parser(clap::value_parser!(usize)),
                         )
+                        .arg(num_taxa_arg())
+                        .arg(
+                            arg!(--objective <OBJECTIVE> "Maximize or minimize PD")
+                                .value_parser(["min", "max"])
+                                .default_value("max"),
+                        )
+                        .arg(
+                            arg!(--time_limit <SECONDS> "Time after which the best set found so far is reported, when groups are not clades")
+                                .value_parser(clap::value_parser!(f64))
+                                .default_value("10"),
+                        ),
                 )
                 .subcommand(
-                    Command::new("all_avg")
-                        .about("Compute all avgPD")
+                    tree_args(Command::new("stratified").about("Compute the taxa set maximizing PD with a minimum number of taxa from every clade at a depth cut"))
+                        .arg(num_taxa_arg())
                         .arg(
-                            arg!(-f --file <TREE_FILE> "Input Tree File")
-                                .required(true)
+                            arg!(--depth <DEPTH> "Distance from the root at which the tree is cut into clades")
+                                .value_parser(clap::value_parser!(f32))
+                                .required_unless_present("clades")
+                                .conflicts_with("clades"),
+                        )
+                        .arg(
+                            arg!(--clades <CLADES_FILE> "File listing on each line comma-separated taxa whose MRCA is the root of a clade")
                                 .value_parser(clap::value_parser!(String)),
-                        )        
+                        )
+                        .arg(
+                            arg!(-m --min_per_clade <MIN> "Fewest taxa to select from every clade")
+                                .value_parser(clap::value_parser!(usize))
+                                .default_value("1"),
+                        ),
+                )
+                .subcommand(
+                    tree_args(Command::new("rank").about("Rank all taxa greedily by marginal gain in maxPD as CSV, without computing the PD tables")),
+                )
+                .subcommand(
+                    tree_args(Command::new("pareto").about("Report the Pareto front between PD and edge count for every k as CSV (of the first resolution when averaging)"))
+                        .arg(
+                            arg!(--objective <OBJECTIVE> "Trade high PD against few edges (max) or low PD against many edges (min)")
+                                .value_parser(["min", "max"])
+                                .default_value("max"),
+                        ),
                 )
 
         )
@@ -95,27 +619,15 @@ fn main() -> Result<()>{
             Command::new("gen")
                 .about("Compute Phylogenetic Diversity")
                 .subcommand(
-                    Command::new("min").about("Compute minPD").arg(
-                        arg!(-f --file <TREE_FILE> "Input Tree File")
-                            .required(true)
-                            .value_parser(clap::value_parser!(String)),
-                    ),
+                    tree_args(Command::new("min").about("Compute minPD"))
+                        .arg(norm_arg()),
                 )
                 .subcommand(
-                    Command::new("max").about("Compute maxPD").arg(
-                        arg!(-f --file <TREE_FILE> "Input Tree File")
-                            .required(true)
-                            .value_parser(clap::value_parser!(String)),
-                    ),
+                    tree_args(Command::new("max").about("Compute maxPD"))
+                        .arg(norm_arg()),
                 )
                 .subcommand(
-                    Command::new("all")
-                        .about("Compute maxPD")
-                        .arg(
-                            arg!(-f --file <TREE_FILE> "Input Tree File")
-                                .required(true)
-                                .value_parser(clap::value_parser!(String)),
-                        )
+                    tree_args(Command::new("all").about("Compute maxPD"))
                 ),
         )
         .about("CLI tool for quick tree operations")
@@ -125,186 +637,427 @@ fn main() -> Result<()>{
         Some(("PD", sub_m)) => {
             match sub_m.subcommand() {
                 Some(("min", min_pd)) => {
-                    let mut tree_file =
-                        File::open(min_pd.get_one::<String>("file").expect("required"))?;
+                    let trees = read_trees(min_pd)?;
+                    let tree = &trees[0];
                     let num_taxa = min_pd.get_one::<usize>("num_taxa").expect("required");
-                    let mut trees = String::new();
-
-                    tree_file.read_to_string(&mut trees).unwrap();
-                    let tree_string = trees.split("\n").collect_vec()[0];
-                    let mut tree = PhyloTree::from_newick(tree_string.as_bytes())?;
-                    if !tree.is_binary(){
-                        binarize_tree(&mut tree);
-                    }
                     let num_taxa = match *num_taxa==0{
-                        true => {println!("setting k to n");tree.num_taxa()},
-                        false => cmp::min(*num_taxa as usize, tree.num_taxa()),
+                        true => {println!("setting k to n");tree.get_leaf_ids().len()},
+                        false => cmp::min(*num_taxa, tree.get_leaf_ids().len()),
                     };
-                    let tree_pd = TreePD::new(&tree);
+                    let tree_pds = build_tree_pds(&trees, min_pd)?;
+                    let tree_pd = &tree_pds[0];
                     println!(
                         "minPD: {}\nnormalized minPD: {}\nminPD set:{}\nnormalized minPD set:{}",
-                        tree_pd.get_minPD(num_taxa.clone()),
-                        tree_pd.get_norm_minPD(num_taxa.clone()),
-                        tree_pd
-                            .get_minPD_taxa_set(num_taxa.clone())
-                            .map(|x| tree.get_node_taxa(x).unwrap())
-                            .join(","),
-                        tree_pd
-                            .get_norm_minPD_taxa_set(num_taxa.clone())
-                            .map(|x| tree.get_node_taxa(x).unwrap())
-                            .join(",")
+                        mean(&tree_pds, |x| x.get_minPD(num_taxa)),
+                        mean(&tree_pds, |x| x.get_norm_minPD(num_taxa)),
+                        tree_pd.get_minPD_taxa_names(num_taxa)?.join(","),
+                        tree_pd.get_norm_minPD_taxa_names(num_taxa)?.join(",")
                     );
-                    // dbg!("{}", tree);
                 },
                 Some(("all_min", min_pd)) => {
-                    let mut tree_file =
-                        File::open(min_pd.get_one::<String>("file").expect("required"))?;
-                    // let num_taxa = min_pd.get_one::<usize>("num_taxa").expect("required");
-                    let mut trees = String::new();
-
-                    tree_file.read_to_string(&mut trees).unwrap();
-                    let tree_string = trees.split("\n").collect_vec()[0];
-                    let mut tree = SimpleRootedTree::from_newick(tree_string.as_bytes())?;
-                    if !tree.is_binary(){
-                        binarize_tree(&mut tree);
-                    }
-                    // let num_taxa = match *num_taxa==0{
-                    //     true => {println!("setting k to n");tree.num_taxa()},
-                    //     false => *num_taxa as usize,
-                    // };
-                    let tree_taxa: usize = tree.num_taxa();
+                    let trees = read_trees(min_pd)?;
+                    let tree = &trees[0];
+                    let tree_taxa: usize = tree.get_leaf_ids().len();
 
-                    let tree_pd = TreePD::new(&tree);
+                    let tree_pds = build_tree_pds(&trees, min_pd)?;
+                    let tree_pd = &tree_pds[0];
                     for num_taxa in 3..tree_taxa+1{
                         println!(
                             "k: {}\nminPD: {}\nnormalized minPD: {}\nminPD set:{}\nnormalized minPD set:{}\n",
                             num_taxa,
-                            tree_pd.get_minPD(num_taxa.clone()),
-                            tree_pd.get_norm_minPD(num_taxa.clone()),
-                            tree_pd
-                                .get_minPD_taxa_set(num_taxa.clone())
-                                .map(|x| tree.get_node_taxa(x).unwrap())
-                                .join(","),
-                            tree_pd
-                                .get_norm_minPD_taxa_set(num_taxa.clone())
-                                .map(|x| tree.get_node_taxa(x).unwrap())
-                                .join(",")
-                        );    
+                            mean(&tree_pds, |x| x.get_minPD(num_taxa)),
+                            mean(&tree_pds, |x| x.get_norm_minPD(num_taxa)),
+                            tree_pd.get_minPD_taxa_names(num_taxa)?.join(","),
+                            tree_pd.get_norm_minPD_taxa_names(num_taxa)?.join(",")
+                        );
                     }
-                    // dbg!("{}", tree);
                 },
                 Some(("max", max_pd)) => {
-                    let mut tree_file =
-                        File::open(max_pd.get_one::<String>("file").expect("required"))?;
+                    let trees = read_trees(max_pd)?;
+                    let tree = &trees[0];
                     let n_taxa = max_pd.get_one::<usize>("num_taxa").expect("required");
-                    let mut trees = String::new();
-
-                    tree_file.read_to_string(&mut trees).unwrap();
-                    let tree_string = trees.split("\n").collect_vec()[0];
-                    let mut tree = SimpleRootedTree::from_newick(tree_string.as_bytes())?;
-                    if !tree.is_binary(){
-                        binarize_tree(&mut tree);
-                    }
                     let num_taxa = match *n_taxa==0{
-                        true => {println!("setting k to n");tree.num_taxa()},
-                        false => cmp::min(*n_taxa, tree.num_taxa()),
+                        true => {println!("setting k to n");tree.get_leaf_ids().len()},
+                        false => cmp::min(*n_taxa, tree.get_leaf_ids().len()),
                     };
 
-                    let tree_pd = TreePD::new(&tree);
+                    let tree_pds = build_tree_pds(&trees, max_pd)?;
+                    let tree_pd = &tree_pds[0];
                     println!(
                         "maxPD: {}\nnormalized maxPD: {}\nmaxPD set:{}\nnormalized maxPD set:{}",
-                        tree_pd.get_maxPD(num_taxa.clone()),
-                        tree_pd.get_norm_maxPD(num_taxa.clone()),
-                        tree_pd
-                            .get_maxPD_taxa_set(num_taxa.clone())
-                            .map(|x| tree.get_node_taxa(x).unwrap())
-                            .join(","),
-                        tree_pd
-                            .get_norm_maxPD_taxa_set(num_taxa.clone())
-                            .map(|x| tree.get_node_taxa(x).unwrap())
-                            .join(",")
+                        mean(&tree_pds, |x| x.get_maxPD(num_taxa)),
+                        mean(&tree_pds, |x| x.get_norm_maxPD(num_taxa)),
+                        tree_pd.get_maxPD_taxa_names(num_taxa)?.join(","),
+                        tree_pd.get_norm_maxPD_taxa_names(num_taxa)?.join(",")
                     );
-                    // dbg!("{}", tree);
                 },
                 Some(("avg", max_pd)) => {
-                    let mut tree_file =
-                        File::open(max_pd.get_one::<String>("file").expect("required"))?;
+                    let trees = read_trees(max_pd)?;
+                    let tree = &trees[0];
                     let n_taxa = max_pd.get_one::<usize>("num_taxa").expect("required");
-                    let mut trees = String::new();
-
-                    tree_file.read_to_string(&mut trees).unwrap();
-                    let tree_string = trees.split("\n").collect_vec()[0];
-                    let mut tree = SimpleRootedTree::from_newick(tree_string.as_bytes())?;
-                    if !tree.is_binary(){
-                        binarize_tree(&mut tree);
-                    }
                     let num_taxa = match *n_taxa==0{
-                        true => {println!("setting k to n");tree.num_taxa()},
-                        false => cmp::min(*n_taxa, tree.num_taxa()),
+                        true => {println!("setting k to n");tree.get_leaf_ids().len()},
+                        false => cmp::min(*n_taxa, tree.get_leaf_ids().len()),
                     };
 
-                    let tree_pd = TreePD::new(&tree);
+                    let tree_pds = build_tree_pds(&trees, max_pd)?;
+                    let nulls = null_norms(&tree_pds, max_pd, |x, norm, samples, seed| get_null_normPD(x, norm, num_taxa, samples, seed))?;
                     println!(
-                        "avgPD: {}",
-                        tree_pd.get_avgPD(num_taxa.clone()),
+                        "avgPD: {}{}",
+                        mean(&tree_pds, |x| x.get_avgPD(num_taxa)),
+                        nulls.map(|x| null_norm_report(&x, num_taxa)).unwrap_or_default(),
                     );
-                    // dbg!("{}", tree);
                 },
                 Some(("all_max", max_pd)) => {
-                    let mut tree_file =
-                        File::open(max_pd.get_one::<String>("file").expect("required"))?;
-                    // let n_taxa = max_pd.get_one::<usize>("num_taxa").expect("required");
-                    let mut trees = String::new();
-
-                    tree_file.read_to_string(&mut trees).unwrap();
-                    let tree_string = trees.split("\n").collect_vec()[0];
-                    let mut tree = SimpleRootedTree::from_newick(tree_string.as_bytes())?;
-                    if !tree.is_binary(){
-                        binarize_tree(&mut tree);
+                    let trees = read_trees(max_pd)?;
+                    let tree = &trees[0];
+                    let tree_taxa: usize = tree.get_leaf_ids().len();
+                    if max_pd.get_one::<String>("backend").expect("default") == "greedy" {
+                        let policy = missing_length_policy(max_pd)?;
+                        let mut profiles = vec![];
+                        for tree in trees.iter() {
+                            let (edge_lengths, num_missing) = weighted_edge_lengths(tree, max_pd)?;
+                            // maxPD sets are nested, so maxPD sums the gains of the ranking
+                            let gains = tree.compute_maxPD_ranking(&edge_lengths).into_iter().map(|(_, gain)| gain);
+                            profiles.push(std::iter::once(0.0).chain(gains).scan(0.0, |pd, gain| {
+                                *pd += gain;
+                                Some(*pd)
+                            }).collect_vec());
+                            if profiles.len() == 1 {
+                                warn_missing_lengths(num_missing, policy);
+                            }
+                        }
+                        for num_taxa in 3..tree_taxa+1{
+                            println!(
+                                "k: {}\nmaxPD: {}\n",
+                                num_taxa,
+                                profiles.iter().map(|x| x[num_taxa]).sum::<f32>() / profiles.len() as f32,
+                            );
+                        }
+                        return Ok(());
                     }
-                    let tree_taxa: usize = tree.num_taxa();
 
-                    let tree_pd = TreePD::new(&tree);
+                    let tree_pds = build_tree_pds(&trees, max_pd)?;
+                    let tree_pd = &tree_pds[0];
                     for num_taxa in 3..tree_taxa+1{
                         println!(
                             "k: {}\nmaxPD: {}\nnormalized maxPD: {}\nmaxPD set:{}\nnormalized maxPD set:{}\n",
                             num_taxa,
-                            tree_pd.get_maxPD(num_taxa.clone()),
-                            tree_pd.get_norm_maxPD(num_taxa.clone()),
-                            tree_pd
-                                .get_maxPD_taxa_set(num_taxa.clone())
-                                .map(|x| tree.get_node_taxa(x).unwrap())
-                                .join(","),
-                            tree_pd
-                                .get_norm_maxPD_taxa_set(num_taxa.clone())
-                                .map(|x| tree.get_node_taxa(x).unwrap())
-                                .join(",")
+                            mean(&tree_pds, |x| x.get_maxPD(num_taxa)),
+                            mean(&tree_pds, |x| x.get_norm_maxPD(num_taxa)),
+                            tree_pd.get_maxPD_taxa_names(num_taxa)?.join(","),
+                            tree_pd.get_norm_maxPD_taxa_names(num_taxa)?.join(",")
                         );
                     }
                 },
                 Some(("all_avg", max_pd)) => {
-                    let mut tree_file =
-                        File::open(max_pd.get_one::<String>("file").expect("required"))?;
-                    // let n_taxa = max_pd.get_one::<usize>("num_taxa").expect("required");
-                    let mut trees = String::new();
-
-                    tree_file.read_to_string(&mut trees).unwrap();
-                    let tree_string = trees.split("\n").collect_vec()[0];
-                    let mut tree = SimpleRootedTree::from_newick(tree_string.as_bytes())?;
-                    if !tree.is_binary(){
-                        binarize_tree(&mut tree);
-                    }
-                    let tree_taxa: usize = tree.num_taxa();
+                    let trees = read_trees(max_pd)?;
+                    let tree_taxa: usize = trees[0].get_leaf_ids().len();
 
-                    let tree_pd = TreePD::new(&tree);
+                    let tree_pds = build_tree_pds(&trees, max_pd)?;
+                    let profiles = null_norms(&tree_pds, max_pd, get_null_normPD_profile)?;
                     for num_taxa in 3..tree_taxa+1{
+                        let nulls = profiles.as_ref().map(|x| x.iter().map(|profile| profile[num_taxa].clone()).collect_vec());
                         println!(
-                            "k: {}\navgPD: {}",
+                            "k: {}\navgPD: {}{}",
                             num_taxa,
-                            tree_pd.get_avgPD(num_taxa.clone()),
+                            mean(&tree_pds, |x| x.get_avgPD(num_taxa)),
+                            nulls.map(|x| null_norm_report(&x, num_taxa)).unwrap_or_default(),
+                        );
+                    }
+                },
+                Some(("clades", clades)) => {
+                    let trees = read_trees(clades)?;
+                    let ks = clades.get_many::<usize>("num_taxa").expect("default").copied().collect_vec();
+
+                    let tree_pds = build_tree_pds(&trees, clades)?;
+                    println!(
+                        "clade,size,total_length,{},{},{},min_genPD,max_genPD",
+                        ks.iter().map(|k| format!("minPD_{}", k)).join(","),
+                        ks.iter().map(|k| format!("maxPD_{}", k)).join(","),
+                        ks.iter().map(|k| format!("avgPD_{}", k)).join(","),
+                    );
+                    for report in tree_pds[0].clade_reports(&ks) {
+                        println!(
+                            "{},{},{},{},{},{},{},{}",
+                            report.label,
+                            report.size,
+                            report.total_length,
+                            report.minPD.iter().join(","),
+                            report.maxPD.iter().join(","),
+                            report.avgPD.iter().join(","),
+                            report.min_genPD.map(|x| x.to_string()).unwrap_or_default(),
+                            report.max_genPD.map(|x| x.to_string()).unwrap_or_default(),
+                        );
+                    }
+                },
+                Some(("complement", complement)) => {
+                    let trees = read_trees(complement)?;
+                    let protected = read_taxa(complement.get_one::<String>("taxa").expect("required"))?;
+
+                    let tree_pds = build_tree_pds(&trees, complement)?;
+                    let complements = tree_pds
+                        .iter()
+                        .map(|tree_pd| tree_pd.get_complement_by_names(&protected))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let num_taxa = cmp::min(
+                        *complement.get_one::<usize>("num_taxa").expect("required"),
+                        complements[0].len() - 1,
+                    );
+                    for k in 1..num_taxa+1{
+                        println!(
+                            "k: {}\nPD gain: {}\nadded set:{}\n",
+                            k,
+                            complements.iter().map(|x| x[k].0).sum::<f32>() / complements.len() as f32,
+                            complements[0][k].1.join(","),
+                        );
+                    }
+                },
+                Some(("robust", robust)) => {
+                    let trees = read_trees(robust)?;
+                    let tree = &trees[0];
+                    let num_taxa = cmp::min(*robust.get_one::<usize>("num_taxa").expect("required"), tree.get_leaf_ids().len());
+                    let removals = *robust.get_one::<usize>("removals").expect("required");
+                    if removals > ROBUST_EXACT_MAX_REMOVALS {
+                        eprintln!("warning: robust maxPD is computed heuristically for more than {} removals", ROBUST_EXACT_MAX_REMOVALS);
+                    }
+
+                    let tree_pds = build_tree_pds(&trees, robust)?;
+                    let tree_pd = &tree_pds[0];
+                    let (_, robust_set) = tree_pd.get_robust_maxPD(num_taxa, removals);
+                    println!(
+                        "robust maxPD: {}\nrobust maxPD set:{}",
+                        mean(&tree_pds, |x| x.get_robust_maxPD(num_taxa, removals).0),
+                        tree_pd.get_taxa_names(robust_set.into_iter())?.join(","),
+                    );
+                },
+                Some(("value", value)) => {
+                    let trees = read_trees(value)?;
+                    let taxon_values = read_values(value.get_one::<String>("values").expect("required"))?;
+                    let num_taxa = *value.get_one::<usize>("num_taxa").expect("required");
+                    let lambdas = value.get_many::<f32>("lambda").expect("default").copied().collect_vec();
+
+                    let tree_pds = build_tree_pds(&trees, value)?;
+                    let tree_pd = &tree_pds[0];
+                    println!("lambda_min,lambda_max,PD,value,taxa");
+                    for interval in tree_pd.get_valued_maxPD_sweep(&taxon_values, num_taxa, lambdas[0], lambdas[1])? {
+                        let selection = interval.selection;
+                        println!(
+                            "{},{},{},{},{}",
+                            interval.lambda_min,
+                            interval.lambda_max,
+                            selection.pd,
+                            selection.value,
+                            tree_pd.get_taxa_names(selection.taxa.into_iter())?.join(";"),
+                        );
+                    }
+                },
+                Some(("viable", viable)) => {
+                    let trees = read_trees(viable)?;
+                    let edges = read_edge_list(viable.get_one::<String>("dependencies").expect("required"))?;
+                    let num_taxa = *viable.get_one::<usize>("num_taxa").expect("required");
+                    let max_nodes = *viable.get_one::<usize>("max_nodes").expect("default");
+
+                    let tree_pds = build_tree_pds(&trees, viable)?;
+                    let tree_pd = &tree_pds[0];
+                    let network = DependencyNetwork::from_edges(tree_pd, &edges)?;
+                    match network.get_viable_maxPD_with_limit(tree_pd, num_taxa, max_nodes) {
+                        Some(selection) => {
+                            if !selection.exact {
+                                eprintln!("warning: search stopped after {} nodes; the reported set may not be optimal", max_nodes);
+                            }
+                            println!(
+                                "viable maxPD: {}\nviable maxPD set:{}",
+                                selection.pd,
+                                tree_pd.get_taxa_names(selection.taxa.into_iter())?.join(","),
+                            );
+                        }
+                        None => println!("no viable set of {} taxa", num_taxa),
+                    }
+                },
+                Some(("ark", ark)) => {
+                    let trees = read_trees(ark)?;
+                    let projects = read_projects(ark.get_one::<String>("projects").expect("required"))?;
+                    let budget = *ark.get_one::<usize>("budget").expect("required");
+
+                    let tree_pds = build_tree_pds(&trees, ark)?;
+                    let solution = solve_noahs_ark(&tree_pds[0], &projects, budget)?;
+                    println!("expected PD: {}\ncost: {}\ntaxon,cost,probability", solution.expected_pd, solution.cost);
+                    for row in solution.projects {
+                        let (taxon, project) = &projects[row];
+                        println!("{},{},{}", taxon, project.cost, project.survival);
+                    }
+                },
+                Some(("reserves", reserves)) => {
+                    let trees = read_trees(reserves)?;
+                    let mut site_rows = read_sites(reserves.get_one::<String>("sites").expect("required"))?;
+                    let budget = match (reserves.get_one::<usize>("num_sites"), reserves.get_one::<f32>("budget")) {
+                        (Some(num_sites), _) => {
+                            site_rows.iter_mut().for_each(|(_, _, cost)| *cost = 1.0);
+                            *num_sites as f32
+                        }
+                        (None, Some(budget)) => *budget,
+                        (None, None) => bail!("either --num_sites or --budget is required"),
+                    };
+
+                    let tree_pds = build_tree_pds(&trees, reserves)?;
+                    let tree_pd = &tree_pds[0];
+                    let sites = Sites::new(
+                        tree_pd,
+                        &site_rows.iter().map(|(_, taxa, cost)| (taxa.clone(), *cost)).collect_vec(),
+                    )?;
+                    let selection = match reserves.get_one::<String>("method").expect("default").as_str() {
+                        "greedy" => sites.get_greedy_reserves(tree_pd, budget),
+                        "exact" => {
+                            let max_nodes = *reserves.get_one::<usize>("max_nodes").expect("default");
+                            let selection = sites.get_exact_reserves(tree_pd, budget, max_nodes);
+                            if !selection.exact {
+                                eprintln!("warning: search stopped after {} nodes; the reported selection may not be optimal", max_nodes);
+                            }
+                            selection
+                        }
+                        _ => sites.get_local_search_reserves(tree_pd, budget),
+                    };
+                    println!(
+                        "PD: {}\ncost: {}\nsites:{}",
+                        selection.pd,
+                        selection.cost,
+                        selection.sites.iter().map(|x| site_rows[*x].0.as_str()).join(","),
+                    );
+                },
+                Some(("conflicts", conflicts)) => {
+                    let trees = read_trees(conflicts)?;
+                    let edges = read_edge_list(conflicts.get_one::<String>("conflicts").expect("required"))?;
+                    let num_taxa = *conflicts.get_one::<usize>("num_taxa").expect("required");
+                    let (op, objective) = match conflicts.get_one::<String>("objective").expect("default").as_str() {
+                        "min" => (cmp::Ordering::Less, "minPD"),
+                        _ => (cmp::Ordering::Greater, "maxPD"),
+                    };
+                    let time_limit = Duration::from_secs_f64(*conflicts.get_one::<f64>("time_limit").expect("default"));
+
+                    let tree_pds = build_tree_pds(&trees, conflicts)?;
+                    let tree_pd = &tree_pds[0];
+                    let graph = ConflictGraph::from_edges(tree_pd, &edges)?;
+                    match graph.get_conflict_free_PD(tree_pd, op, num_taxa, time_limit) {
+                        Some(selection) => {
+                            if !selection.exact {
+                                eprintln!("warning: time limit reached; the reported set may not be optimal");
+                            }
+                            println!(
+                                "conflict-free {}: {}\nconflict-free {} set:{}",
+                                objective,
+                                selection.pd,
+                                objective,
+                                tree_pd.get_taxa_names(selection.taxa.into_iter())?.join(","),
+                            );
+                        }
+                        None => println!("no conflict-free set of {} taxa found", num_taxa),
+                    }
+                },
+                Some(("quotas", quotas)) => {
+                    let trees = read_trees(quotas)?;
+                    let default_quota = GroupQuota {
+                        min: *quotas.get_one::<usize>("at_least").expect("default"),
+                        max: quotas.get_one::<usize>("at_most").copied().unwrap_or(usize::MAX),
+                    };
+                    let group_rows = read_groups(
+                        quotas.get_one::<String>("groups").expect("required"),
+                        quotas.get_one::<String>("quotas"),
+                        default_quota,
+                    )?;
+                    let num_taxa = *quotas.get_one::<usize>("num_taxa").expect("required");
+                    let (op, objective) = match quotas.get_one::<String>("objective").expect("default").as_str() {
+                        "min" => (cmp::Ordering::Less, "minPD"),
+                        _ => (cmp::Ordering::Greater, "maxPD"),
+                    };
+                    let time_limit = Duration::from_secs_f64(*quotas.get_one::<f64>("time_limit").expect("default"));
+
+                    let tree_pds = build_tree_pds(&trees, quotas)?;
+                    let tree_pd = &tree_pds[0];
+                    let groups = Groups::new(tree_pd, &group_rows)?;
+                    match groups.get_quota_PD(tree_pd, op, num_taxa, time_limit) {
+                        Some(selection) => {
+                            if !selection.exact {
+                                eprintln!("warning: time limit reached; the reported set may not be optimal");
+                            }
+                            println!(
+                                "{}: {}\n{} set:{}",
+                                objective,
+                                selection.pd,
+                                objective,
+                                tree_pd.get_taxa_names(selection.taxa.into_iter())?.join(","),
+                            );
+                        }
+                        None => println!("no set of {} taxa meeting the quotas found", num_taxa),
+                    }
+                },
+                Some(("stratified", stratified)) => {
+                    let trees = read_trees(stratified)?;
+                    let num_taxa = *stratified.get_one::<usize>("num_taxa").expect("required");
+                    let min_per_clade = *stratified.get_one::<usize>("min_per_clade").expect("default");
+
+                    let tree_pds = build_tree_pds(&trees, stratified)?;
+                    let tree_pd = &tree_pds[0];
+                    let clade_ids = match stratified.get_one::<String>("clades") {
+                        Some(path) => read_clades(path)?
+                            .iter()
+                            .map(|taxa| tree_pd.get_clade_id(taxa))
+                            .collect::<Result<Vec<_>, _>>()?,
+                        None => tree_pd.get_depth_cut(*stratified.get_one::<f32>("depth").expect("required")),
+                    };
+                    println!("clades: {}", clade_ids.len());
+                    match tree_pd.get_stratified_maxPD(num_taxa, &clade_ids, min_per_clade) {
+                        Some((pd, taxa)) => println!(
+                            "stratified maxPD: {}\nstratified maxPD set:{}",
+                            pd,
+                            tree_pd.get_taxa_names(taxa.into_iter())?.join(","),
+                        ),
+                        None => println!("no set of {} taxa covers every clade", num_taxa),
+                    }
+                },
+                Some(("rank", rank)) => {
+                    let trees = read_trees(rank)?;
+                    let tree = &trees[0];
+                    let policy = missing_length_policy(rank)?;
+                    let (edge_lengths, num_missing) = weighted_edge_lengths(tree, rank)?;
+                    warn_missing_lengths(num_missing, policy);
+
+                    println!("rank,taxon,gain,maxPD");
+                    let mut max_pd = 0.0;
+                    for (rank, (leaf_id, gain)) in tree.compute_maxPD_ranking(&edge_lengths).into_iter().enumerate() {
+                        max_pd += gain;
+                        println!(
+                            "{},{},{},{}",
+                            rank + 1,
+                            tree.get_node_taxa(leaf_id).map(|x| x.as_str()).unwrap_or_default(),
+                            gain,
+                            max_pd,
                         );
                     }
                 },
+                Some(("pareto", pareto)) => {
+                    let trees = read_trees(pareto)?;
+                    let op = match pareto.get_one::<String>("objective").expect("default").as_str() {
+                        "min" => cmp::Ordering::Less,
+                        _ => cmp::Ordering::Greater,
+                    };
+
+                    let tree_pds = build_tree_pds(&trees, pareto)?;
+                    let tree_pd = &tree_pds[0];
+                    println!("k,PD,edges,normalized_PD,taxa");
+                    for (num_taxa, front) in tree_pd.get_pareto_front(op).into_iter().enumerate().skip(1) {
+                        for point in front {
+                            println!(
+                                "{},{},{},{},{}",
+                                num_taxa,
+                                point.pd,
+                                point.num_edges,
+                                point.pd / cmp::max(point.num_edges, 1) as f32,
+                                tree_pd.get_taxa_names(point.taxa.into_iter())?.join(";"),
+                            );
+                        }
+                    }
+                },
 
                 _ => println!("No valid PD metric chosen! Refer help page (-h flag)"),
             }
@@ -312,81 +1065,54 @@ fn main() -> Result<()>{
         Some(("gen", sub_m)) => {
             match sub_m.subcommand() {
                 Some(("min", min_pd)) => {
-                    let mut tree_file =
-                        File::open(min_pd.get_one::<String>("file").expect("required"))?;
-                    let mut trees = String::new();
-
-                    tree_file.read_to_string(&mut trees).unwrap();
-                    let tree_string = trees.split("\n").collect_vec()[0];
-                    let mut tree = SimpleRootedTree::from_newick(tree_string.as_bytes())?;
-                    if !tree.is_binary(){
-                        binarize_tree(&mut tree);
-                    }
-                    let num_taxa = tree.num_taxa();
-                    let tree_pd = TreePD::new(&tree);
-                    match num_taxa >=3{
-                        true => {
+                    let trees = read_trees(min_pd)?;
+                    let norm = min_pd.get_one::<String>("norm").expect("default").parse::<NormalizationKind>()?;
+                    let tree_pds = build_tree_pds(&trees, min_pd)?;
+                    let tree_pd = &tree_pds[0];
+                    match tree_pd.get_min_genPD_with_norm(&norm){
+                        Some((_, genpd_set)) => {
                             println!(
                                 "minGenPD: {}\nminGenPD set: {}\nminGenPD set size: {}",
-                                tree_pd.get_min_genPD(),
-                                tree_pd
-                                    .get_min_genPD_set()
-                                    .map(|x| tree.get_node_taxa(x).unwrap())
-                                    .join(","),
-                                tree_pd.get_min_genPD_set().collect_vec().len()
+                                mean(&tree_pds, |x| x.get_min_genPD_with_norm(&norm).map_or(0.0, |y| y.0)),
+                                tree_pd.get_taxa_names(genpd_set.iter().copied())?.join(","),
+                                genpd_set.len()
                             );
-        
+
                         },
-                        false => {
+                        None => {
                             println!(
-                                "minGenPD: {}\nminGenPD set: {}\nminGenPD set size: {}",
+                                "minGenPD: {}\nminGenPD set: \nminGenPD set size: {}",
                                 0,
-                                "",
                                 0
                             );
-        
+
                         }
                     }
-                    // dbg!("{}", tree);
                 },
                 Some(("max", max_pd)) => {
-                    let mut tree_file =
-                        File::open(max_pd.get_one::<String>("file").expect("required"))?;
-                    let mut trees = String::new();
-
-                    tree_file.read_to_string(&mut trees).unwrap();
-                    let tree_string = trees.split("\n").collect_vec()[0];
-                    let mut tree = SimpleRootedTree::from_newick(tree_string.as_bytes())?;
-                    if !tree.is_binary(){
-                        binarize_tree(&mut tree);
-                    }
-                    let num_taxa = tree.num_taxa();
-                    let tree_pd = TreePD::new(&tree);
-                    match num_taxa >=3{
-                        true => {
+                    let trees = read_trees(max_pd)?;
+                    let norm = max_pd.get_one::<String>("norm").expect("default").parse::<NormalizationKind>()?;
+                    let tree_pds = build_tree_pds(&trees, max_pd)?;
+                    let tree_pd = &tree_pds[0];
+                    match tree_pd.get_max_genPD_with_norm(&norm){
+                        Some((_, genpd_set)) => {
                             println!(
                                 "maxGenPD: {}\nmaxGenPD set: {}\nmaxGenPD set size: {}",
-                                tree_pd.get_max_genPD(),
-                                tree_pd
-                                    .get_max_genPD_set()
-                                    .map(|x| tree.get_node_taxa(x).unwrap())
-                                    .join(","),
-                                tree_pd.get_max_genPD_set().collect_vec().len()
+                                mean(&tree_pds, |x| x.get_max_genPD_with_norm(&norm).map_or(0.0, |y| y.0)),
+                                tree_pd.get_taxa_names(genpd_set.iter().copied())?.join(","),
+                                genpd_set.len()
                             );
-                
+
                         },
-                        false => {
+                        None => {
                             println!(
-                                "maxGenPD: {}\nmaxGenPD set: {}\nmaxGenPD set size: {}",
+                                "maxGenPD: {}\nmaxGenPD set: \nmaxGenPD set size: {}",
                                 0,
-                                "",
                                 0
                             );
-        
+
                         }
                     }
-
-                    // dbg!("{}", tree);
                 },
                 _ => println!("No valid PD metric chosen! Refer help page (-h flag)"),
             }
@@ -1 +1,2 @@
+#![allow(non_snake_case, clippy::type_complexity)]
 pub mod pd;
@@ -1,10 +1,21 @@
+pub mod conflicts;
+pub mod dependencies;
+pub mod error;
+pub mod groups;
+pub mod noahs_ark;
+pub mod normalization;
+pub mod null_model;
 pub mod phylogenetic_diversity;
-use crate::pd::phylogenetic_diversity::{RootedPhylogeneticDiversity, TreePDMap, binary_splits};
+pub mod reserves;
+use crate::pd::error::PDError;
+use crate::pd::normalization::{Normalization, TaxaSetSummary};
+use crate::pd::phylogenetic_diversity::{RootedPhylogeneticDiversity, TreePDMap, MissingLengthPolicy, NodeWeightPolicy, PDOptions, TieBreak, CladeReport, ParetoPoint, ValuedInterval, ValuedSelection, ROBUST_BEAM_WIDTH, ROBUST_EXACT_MAX_REMOVALS, TIE_ULPS, mrca_id};
 use itertools::Itertools;
 use phylo::prelude::*;
 use phylo::tree::SimpleRootedTree;
 use phylogenetic_diversity::pascal_triangle;
 use std::cmp::{Ordering, min, max};
+use std::collections::{HashMap, HashSet};
 
 pub struct TreePD<'a,T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> {
     tree: &'a SimpleRootedTree<T,W,Z>,
@@ -17,14 +28,43 @@ pub struct TreePD<'a,T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> {
     precomputed_norm_max: Vec<Vec<(W, u32)>>,
     precomputed_norm_max_set: Vec<Vec<Vec<usize>>>,
     precomputed_avg: Vec<Vec<W>>,
+    num_missing_lengths: usize,
+    taxon_index: Result<HashMap<T, usize>, PDError>,
+    label_ranks: Vec<usize>,
+    edge_lengths: Vec<W>,
+    options: PDOptions,
 }
 
 impl<'a,T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> TreePD<'a,T,W,Z> {
+    /// Precomputes all PD tables, treating missing branch lengths as zero
     pub fn new(tree: &'a SimpleRootedTree<T,W,Z>) -> Self {
-        let (min, min_set, min_norm, min_norm_set) = tree.compute_norm_min();
-        let (max, max_set, max_norm, max_norm_set) = tree.compute_norm_max();
-        let avg = tree.compute_avg();
-        TreePD {
+        Self::with_missing_length_policy(tree, MissingLengthPolicy::Zero)
+            .expect("treating missing lengths as zero cannot fail")
+    }
+
+    /// Precomputes all PD tables, resolving missing branch lengths with the provided policy
+    pub fn with_missing_length_policy(
+        tree: &'a SimpleRootedTree<T,W,Z>,
+        policy: MissingLengthPolicy,
+    ) -> Result<Self, PDError> {
+        Self::with_options(tree, PDOptions { missing_lengths: policy, ..Default::default() })
+    }
+
+    /// Precomputes all PD tables with the provided options. With
+    /// `NodeWeightPolicy::Cost`, weighted edge lengths may be negative; maxPD and minPD
+    /// are then no longer monotone in the number of taxa, and the computations that
+    /// need non-negative lengths (reserve selection, Noah's Ark) return
+    /// `PDError::NegativeEdgeLengths`.
+    pub fn with_options(
+        tree: &'a SimpleRootedTree<T,W,Z>,
+        options: PDOptions,
+    ) -> Result<Self, PDError> {
+        let (mut edge_lengths, num_missing_lengths) = tree.resolve_edge_lengths(options.missing_lengths)?;
+        tree.apply_node_weights(&mut edge_lengths, options.node_weights);
+        let (min, min_set, min_norm, min_norm_set) = tree.compute_norm_min(&edge_lengths, options.tie_break);
+        let (max, max_set, max_norm, max_norm_set) = tree.compute_norm_max(&edge_lengths, options.tie_break);
+        let avg = tree.compute_avg(&edge_lengths);
+        Ok(TreePD {
             tree,
             precomputed_min: min,
             precomputed_min_set: min_set,
@@ -35,8 +75,504 @@ impl<'a,T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> TreePD<'a,T,W,Z> {
             precomputed_norm_max: max_norm,
             precomputed_norm_max_set: max_norm_set,
             precomputed_avg: avg,
+            num_missing_lengths,
+            taxon_index: taxon_index(tree),
+            label_ranks: label_ranks(tree),
+            edge_lengths,
+            options,
+        })
+    }
+
+    /// Whether a candidate set is preferred over an incumbent set of equal value under
+    /// the tie-breaking rule, with both sets in any order
+    fn prefers_set(&self, candidate: &[usize], incumbent: &[usize]) -> bool {
+        let sorted = |set: &[usize]| set.iter().copied().sorted_by_key(|x| self.label_ranks[*x]).collect_vec();
+        prefers(&self.label_ranks, self.options.tie_break, (0, &sorted(candidate)), (0, &sorted(incumbent)))
+    }
+
+    /// Fails if node weights made any edge length negative
+    fn check_non_negative_lengths(&self) -> Result<(), PDError> {
+        match self.edge_lengths.iter().filter(|x| **x < W::zero()).count() {
+            0 => Ok(()),
+            num_negative => Err(PDError::NegativeEdgeLengths { num_negative }),
         }
     }
+
+    /// Returns the number of edges that had no branch length in the input tree
+    pub fn num_missing_lengths(&self) -> usize {
+        self.num_missing_lengths
+    }
+
+    /// Returns the NodeID of the leaf with a taxon label, failing if the label is
+    /// unknown or leaf labels are not unique
+    pub fn get_taxon_id(&self, taxon: &T) -> Result<usize, PDError> {
+        self.taxon_index
+            .as_ref()
+            .map_err(Clone::clone)?
+            .get(taxon)
+            .copied()
+            .ok_or_else(|| PDError::UnknownTaxon(taxon.to_string()))
+    }
+
+    /// Translates leaf NodeIDs into taxon labels, failing if leaf labels are not unique.
+    /// The `*_names` getters below cover the root-level queries; for a clade, find its
+    /// NodeID with `get_clade_id` and translate the sets of the `*_node` getters or
+    /// clade reports with this method.
+    pub fn get_taxa_names(&self, node_ids: impl Iterator<Item = usize>) -> Result<Vec<T>, PDError> {
+        self.taxon_index.as_ref().map_err(Clone::clone)?;
+        Ok(node_ids
+            .map(|node_id| self.tree.get_node_taxa(node_id).unwrap().clone())
+            .collect())
+    }
+
+    /// Returns the taxon labels of a minPD set
+    pub fn get_minPD_taxa_names(&self, num_taxa: usize) -> Result<Vec<T>, PDError> {
+        self.get_taxa_names(self.get_minPD_taxa_set(num_taxa))
+    }
+
+    /// Returns the taxon labels of a normalized minPD set
+    pub fn get_norm_minPD_taxa_names(&self, num_taxa: usize) -> Result<Vec<T>, PDError> {
+        self.get_taxa_names(self.get_norm_minPD_taxa_set(num_taxa))
+    }
+
+    /// Returns the taxon labels of the minimum generalized PD set, or `None` if no set
+    /// of at least three taxa has non-zero PD
+    pub fn get_min_genPD_names(&self) -> Result<Option<Vec<T>>, PDError> {
+        self.get_min_genPD_set().map(|x| self.get_taxa_names(x)).transpose()
+    }
+
+    /// Returns the taxon labels of a maxPD set
+    pub fn get_maxPD_taxa_names(&self, num_taxa: usize) -> Result<Vec<T>, PDError> {
+        self.get_taxa_names(self.get_maxPD_taxa_set(num_taxa))
+    }
+
+    /// Returns the taxon labels of a normalized maxPD set
+    pub fn get_norm_maxPD_taxa_names(&self, num_taxa: usize) -> Result<Vec<T>, PDError> {
+        self.get_taxa_names(self.get_norm_maxPD_taxa_set(num_taxa))
+    }
+
+    /// Returns the taxon labels of the maximum generalized PD set, or `None` if no set
+    /// of at least three taxa has non-zero PD
+    pub fn get_max_genPD_names(&self) -> Result<Option<Vec<T>>, PDError> {
+        self.get_max_genPD_set().map(|x| self.get_taxa_names(x)).transpose()
+    }
+
+    /// Returns, for every number of taxa, the set maximizing PD plus `lambda` times the
+    /// total value of its taxa. Taxa missing from `values` have value zero.
+    pub fn get_valued_maxPD(&self, values: &HashMap<T, W>, lambda: W) -> Result<Vec<ValuedSelection<W>>, PDError> {
+        Ok(self.valued_maxPD(&self.taxon_values(values)?, lambda))
+    }
+
+    /// Returns the sets of a number of taxa maximizing PD plus `lambda` times the total
+    /// value of their taxa as `lambda` goes from `lambda_min` to `lambda_max`, each
+    /// with the interval of `lambda` over which it stays optimal. The objective of a
+    /// set is a line in `lambda`, so the breakpoints are where consecutive lines of
+    /// their upper envelope cross, found by solving the DP at the crossing of the lines
+    /// optimal at both ends of an interval until no other set beats them there.
+    pub fn get_valued_maxPD_sweep(
+        &self,
+        values: &HashMap<T, W>,
+        num_taxa: usize,
+        lambda_min: W,
+        lambda_max: W,
+    ) -> Result<Vec<ValuedInterval<W>>, PDError> {
+        let taxon_values = self.taxon_values(values)?;
+        let (lambda_min, lambda_max) = (lambda_min.min(lambda_max), lambda_min.max(lambda_max));
+        let select = |lambda: W| {
+            let mut selections = self.valued_maxPD(&taxon_values, lambda);
+            selections.swap_remove(min(num_taxa, selections.len() - 1))
+        };
+        // optimal sets in order of increasing lambda, with the lambda they become optimal at
+        let mut envelope = vec![(lambda_min, select(lambda_min))];
+        let mut pending = vec![select(lambda_max)];
+        while let Some(upper) = pending.pop() {
+            let (lambda_lower, lower) = envelope.last().unwrap();
+            if upper.taxa == lower.taxa {
+                continue;
+            }
+            // the lines cross where the set optimal at the upper end takes over
+            let crossing = ((lower.pd - upper.pd) / (upper.value - lower.value))
+                .max(*lambda_lower)
+                .min(upper.lambda);
+            let middle = select(crossing);
+            if middle.taxa == lower.taxa || middle.taxa == upper.taxa {
+                envelope.push((crossing, upper));
+            } else {
+                pending.push(upper);
+                pending.push(middle);
+            }
+        }
+        let ends = envelope.iter().skip(1).map(|(lambda, _)| *lambda).chain([lambda_max]).collect_vec();
+        Ok(envelope
+            .into_iter()
+            .zip(ends)
+            .filter(|((start, _), end)| start < end || lambda_min == lambda_max)
+            .map(|((lambda_min, selection), lambda_max)| ValuedInterval {
+                lambda_min,
+                lambda_max,
+                selection: ValuedSelection {
+                    lambda: lambda_min,
+                    objective: selection.pd + lambda_min * selection.value,
+                    ..selection
+                },
+            })
+            .collect())
+    }
+
+    /// Value of every taxon, indexed by NodeID
+    fn taxon_values(&self, values: &HashMap<T, W>) -> Result<Vec<W>, PDError> {
+        let mut taxon_values = vec![W::zero(); self.edge_lengths.len()];
+        for (taxon, value) in values {
+            taxon_values[self.get_taxon_id(taxon)?] = *value;
+        }
+        Ok(taxon_values)
+    }
+
+    fn valued_maxPD(&self, taxon_values: &[W], lambda: W) -> Vec<ValuedSelection<W>> {
+        let leaf_values = taxon_values.iter().map(|value| lambda * *value).collect_vec();
+        self.tree
+            .compute_valued_maxPD(&self.edge_lengths, &leaf_values, self.options.tie_break)
+            .into_iter()
+            .map(|(objective, taxa)| ValuedSelection {
+                lambda,
+                objective,
+                pd: self.taxa_set_pd(&taxa),
+                value: taxa.iter().map(|leaf_id| taxon_values[*leaf_id]).sum(),
+                taxa,
+            })
+            .collect()
+    }
+
+    /// Returns the roots of the clades obtained by cutting the tree at a distance from
+    /// the root: the nodes at or below the cut whose parent is above it. Leaves above
+    /// the cut belong to no clade. Distances are measured along the branch lengths
+    /// (with missing lengths resolved), without node weights.
+    pub fn get_depth_cut(&self, depth: W) -> Vec<usize> {
+        let root_id = self.tree.get_root_id();
+        if depth <= W::zero() {
+            return vec![root_id];
+        }
+        let (branch_lengths, _) = self
+            .tree
+            .resolve_edge_lengths(self.options.missing_lengths)
+            .expect("missing lengths were resolved when building the tables");
+        let mut depths = vec![W::zero(); branch_lengths.len()];
+        let mut clade_ids = vec![];
+        for node_id in self.tree.preord_ids(root_id) {
+            if let Some(parent_id) = self.tree.get_node_parent_id(node_id) {
+                depths[node_id] = depths[parent_id] + branch_lengths[node_id];
+                if depths[parent_id] < depth && depths[node_id] >= depth {
+                    clade_ids.push(node_id);
+                }
+            }
+        }
+        clade_ids
+    }
+
+    /// Returns a set of a number of taxa maximizing PD among sets with at least
+    /// `min_per_clade` taxa in each of the clades below the given nodes (or every taxon
+    /// of smaller clades), or None if there is no such set. The constraints are
+    /// enforced at the clade roots by the PD DP.
+    pub fn get_stratified_maxPD(
+        &self,
+        num_taxa: usize,
+        clade_ids: &[usize],
+        min_per_clade: usize,
+    ) -> Option<(W, Vec<usize>)> {
+        let mut min_counts = vec![0; self.edge_lengths.len()];
+        for clade_id in clade_ids {
+            min_counts[*clade_id] = max(min_counts[*clade_id], min(min_per_clade, self.tree.get_cluster_size(*clade_id)));
+        }
+        clade_constrained_dp(
+            self.tree,
+            Ordering::Greater,
+            &self.edge_lengths,
+            |_| Some(W::zero()),
+            |node_id, count| count >= min_counts[node_id],
+            self.options.tie_break,
+        )
+        .into_iter()
+        .nth(num_taxa)?
+    }
+
+    /// Returns a set of taxa maximizing the PD remaining after any `removals` of them are
+    /// lost, along with that worst-case PD
+    pub fn get_robust_maxPD(&self, num_taxa: usize, removals: usize) -> (W, Vec<usize>) {
+        self.tree.compute_robust_maxPD(&self.edge_lengths, num_taxa, removals, self.options.tie_break)
+    }
+
+    /// Returns, for every number of additional taxa, the largest gain in PD over a
+    /// protected set of leaves and the taxa achieving it
+    pub fn get_complement(&self, protected: &[usize]) -> Vec<(W, Vec<usize>)> {
+        self.tree.compute_complement(&self.edge_lengths, protected, self.options.tie_break)
+    }
+
+    /// Returns, for every number of additional taxa, the largest gain in PD over a
+    /// protected set of taxa and the taxa achieving it, by taxon label
+    pub fn get_complement_by_names(&self, protected: &[T]) -> Result<Vec<(W, Vec<T>)>, PDError> {
+        let protected_ids = protected
+            .iter()
+            .map(|taxon| self.get_taxon_id(taxon))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.get_complement(&protected_ids)
+            .into_iter()
+            .map(|(gain, taxa)| Ok((gain, self.get_taxa_names(taxa.into_iter())?)))
+            .collect()
+    }
+
+    /// Ranks the leaves greedily by their marginal gain in PD, so that the first k
+    /// leaves form a maxPD set for every k. Requires non-negative edge lengths, for
+    /// which maxPD sets are nested across k.
+    pub fn get_maxPD_ranking(&self) -> Vec<(usize, W)> {
+        self.tree.compute_maxPD_ranking(&self.edge_lengths)
+    }
+
+    /// Returns, for every number of taxa, the taxa sets on the Pareto front between
+    /// PD and the number of positive-length edges they span, sorted by PD with the
+    /// best last. `Ordering::Greater` trades high PD against few edges (as maximum
+    /// normalized PD does) and `Ordering::Less` low PD against many edges.
+    pub fn get_pareto_front(&self, op: Ordering) -> Vec<Vec<ParetoPoint<W>>> {
+        self.tree
+            .compute_pareto_front(op, &self.edge_lengths, self.options.tie_break)
+            .into_iter()
+            .map(|front| {
+                front
+                    .into_iter()
+                    .map(|(pd, num_edges, taxa)| ParetoPoint { pd, num_edges, taxa })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the minimum generalized PD under a normalization, along with its taxa set,
+    /// or `None` if no set of at least three taxa has a finite, non-zero normalized PD.
+    /// See `get_max_genPD_with_norm` for the sets compared.
+    pub fn get_min_genPD_with_norm(&self, norm: &impl Normalization<W>) -> Option<(W, Vec<usize>)> {
+        self.genPD_with_norm(norm, Ordering::Less)
+    }
+
+    /// Returns the maximum generalized PD under a normalization, along with its taxa set,
+    /// or `None` if no set of at least three taxa has a finite, non-zero normalized PD.
+    ///
+    /// For every number of taxa (of at least three), the maxPD and normalized maxPD
+    /// sets are normalized and compared. For normalizations that depend on the most
+    /// recent common ancestor of the set, so are the maxPD sets whose most recent
+    /// common ancestor is each internal node. The result is exact for normalizations
+    /// that grow with PD and whose denominator is fixed by the number of taxa (`Taxa`,
+    /// `TreeLength`) or by the ancestor (`MrcaClade`). `Edges` follows the normalized
+    /// PD tables and matches `get_max_genPD`; these optimize over unions of optimal PD
+    /// sets of the children of every node, so they are a heuristic that may miss the
+    /// optimum (which lies on the fronts of `get_pareto_front`). The crown age of a set
+    /// also depends on its deepest taxon, so `CrownAge` is exact on ultrametric trees
+    /// and otherwise the best of the sets compared, as is any other normalization.
+    pub fn get_max_genPD_with_norm(&self, norm: &impl Normalization<W>) -> Option<(W, Vec<usize>)> {
+        self.genPD_with_norm(norm, Ordering::Greater)
+    }
+
+    fn genPD_with_norm(&self, norm: &impl Normalization<W>, op: Ordering) -> Option<(W, Vec<usize>)> {
+        let root_id = self.tree.get_root_id();
+        let (bar, bar_sets, hat, hat_sets) = match op {
+            Ordering::Greater => (&self.precomputed_max, &self.precomputed_max_set, &self.precomputed_norm_max, &self.precomputed_norm_max_set),
+            _ => (&self.precomputed_min, &self.precomputed_min_set, &self.precomputed_norm_min, &self.precomputed_norm_min_set),
+        };
+        let tree_length = self.edge_lengths.iter().copied().sum::<W>();
+        let mut best: Option<(W, Vec<usize>)> = None;
+        let mut compare = |taxa: &[usize], num_edges: u32| {
+            let value = norm.normalize(&self.taxa_set_summary(taxa, num_edges, tree_length));
+            if value == W::zero() || !value.is_finite() {
+                return;
+            }
+            let improves = match &best {
+                None => true,
+                Some((best_value, _)) => value.partial_cmp(best_value) == Some(op),
+            };
+            if improves {
+                best = Some((value, taxa.to_vec()));
+            }
+        };
+        for num_taxa in 3..bar[root_id].len() {
+            let candidates = [
+                (hat[root_id][num_taxa].1, &hat_sets[root_id][num_taxa]),
+                (bar[root_id][num_taxa].1, &bar_sets[root_id][num_taxa]),
+            ];
+            for (num_edges, taxa) in candidates {
+                if taxa.len() == num_taxa {
+                    compare(taxa, num_edges);
+                }
+            }
+        }
+        if norm.depends_on_mrca() {
+            self.for_each_mrca_optimal_set(op, compare);
+        }
+        best
+    }
+
+    /// Calls `visit` with every optimal (max or min, by `op`) PD set of at least three
+    /// taxa among the sets whose most recent common ancestor is an internal node, for
+    /// every internal node and number of taxa, along with the number of edges it spans.
+    /// Such sets have taxa below at least two children of the node.
+    fn for_each_mrca_optimal_set(&self, op: Ordering, mut visit: impl FnMut(&[usize], u32)) {
+        let (bar, bar_sets) = match op {
+            Ordering::Greater => (&self.precomputed_max, &self.precomputed_max_set),
+            _ => (&self.precomputed_min, &self.precomputed_min_set),
+        };
+        let root_id = self.tree.get_root_id();
+        let counts = edge_counts(self.tree, &self.edge_lengths);
+        // edges spanned on the path from a node up to the root
+        let mut path_counts = vec![0_u32; counts.len()];
+        for node_id in self.tree.preord_ids(root_id) {
+            if let Some(parent_id) = self.tree.get_node_parent_id(node_id) {
+                path_counts[node_id] = path_counts[parent_id] + counts[node_id];
+            }
+        }
+        let ranks = &self.label_ranks;
+        let offer = |entry: &mut Option<(W, u32, Vec<usize>)>, val: W, e: u32, set: &dyn Fn() -> Vec<usize>| {
+            let replace = match entry {
+                None => true,
+                Some((best_val, best_e, best_set)) => improves(op, val, *best_val, || {
+                    prefers(ranks, self.options.tie_break, (e, &set()), (*best_e, best_set))
+                }),
+            };
+            if replace {
+                *entry = Some((val, e, set()));
+            }
+        };
+        for node_id in self.tree.postord_ids(root_id) {
+            if self.tree.is_leaf(node_id) {
+                continue;
+            }
+            // optimal sets with taxa below exactly one, and below at least two, of the
+            // children merged so far
+            let mut one: Vec<Option<(W, u32, Vec<usize>)>> = vec![None];
+            let mut many: Vec<Option<(W, u32, Vec<usize>)>> = vec![None];
+            for child_id in self.tree.get_node_children_ids(node_id).collect_vec() {
+                let child_size = self.tree.get_cluster_size(child_id);
+                let child_entry = |r: usize| {
+                    let (val, e) = bar[child_id][r];
+                    (val + self.edge_lengths[child_id], e + counts[child_id], &bar_sets[child_id][r])
+                };
+                let mut next_one = one.clone();
+                let mut next_many = many.clone();
+                next_one.resize(one.len() + child_size, None);
+                next_many.resize(one.len() + child_size, None);
+                for (r, entry) in next_one.iter_mut().enumerate().take(child_size + 1).skip(1) {
+                    let (val, e, set) = child_entry(r);
+                    offer(entry, val, e, &|| set.clone());
+                }
+                for (l, (val_l, e_l, set_l)) in [&one, &many]
+                    .into_iter()
+                    .flat_map(|table| table.iter().enumerate())
+                    .filter_map(|(l, entry)| Some((l, entry.as_ref()?)))
+                {
+                    for r in 1..=child_size {
+                        let (val_r, e_r, set_r) = child_entry(r);
+                        let set = || merge_sets(ranks, set_l, set_r);
+                        offer(&mut next_many[l + r], *val_l + val_r, e_l + e_r, &set);
+                    }
+                }
+                one = next_one;
+                many = next_many;
+            }
+            for (taxa, num_edges) in many.iter().skip(3).flatten().map(|(_, e, taxa)| (taxa, e + path_counts[node_id])) {
+                visit(taxa, num_edges);
+            }
+        }
+    }
+
+    /// Summarizes a taxa set spanning a number of edges for normalization
+    fn taxa_set_summary(&self, taxa: &[usize], num_edges: u32, tree_length: W) -> TaxaSetSummary<W> {
+        let mrca_id = mrca_id(self.tree, taxa);
+        TaxaSetSummary {
+            pd: self.taxa_set_pd(taxa),
+            num_taxa: taxa.len(),
+            num_edges,
+            mrca_clade_pd: self.taxa_set_pd(&self.tree.get_cluster_ids(mrca_id).collect_vec()),
+            tree_length,
+            crown_age: taxa
+                .iter()
+                .map(|leaf_id| self.distance_to_ancestor(*leaf_id, mrca_id))
+                .fold(W::zero(), W::max),
+        }
+    }
+
+    /// Rooted PD of a set of nodes
+    fn taxa_set_pd(&self, taxa: &[usize]) -> W {
+        let mut visited = HashSet::new();
+        let mut pd = W::zero();
+        for leaf_id in taxa {
+            let mut node_id = *leaf_id;
+            while let Some(parent_id) = self.tree.get_node_parent_id(node_id) {
+                if !visited.insert(node_id) {
+                    break;
+                }
+                pd = pd + self.edge_lengths[node_id];
+                node_id = parent_id;
+            }
+        }
+        pd
+    }
+
+    /// Length of the path from a node up to one of its ancestors
+    fn distance_to_ancestor(&self, node_id: usize, ancestor_id: usize) -> W {
+        let mut distance = W::zero();
+        let mut node_id = node_id;
+        while node_id != ancestor_id {
+            distance = distance + self.edge_lengths[node_id];
+            node_id = self.tree.get_node_parent_id(node_id).unwrap();
+        }
+        distance
+    }
+
+    /// Summarizes every internal node of the tree in preorder, with min, max and avg PD
+    /// reported for each of the provided numbers of taxa
+    pub fn clade_reports<'b>(&'b self, ks: &'b [usize]) -> impl Iterator<Item = CladeReport<W>> + 'b {
+        self.tree
+            .preord_ids(self.tree.get_root_id())
+            .filter(|node_id| !self.tree.is_leaf(*node_id))
+            .map(move |node_id| {
+                let size = self.tree.get_cluster_size(node_id);
+                CladeReport {
+                    node_id,
+                    label: self.clade_label(node_id),
+                    size,
+                    total_length: self.get_maxPD_node(node_id, size),
+                    minPD: ks.iter().map(|k| self.get_minPD_node(node_id, *k)).collect(),
+                    maxPD: ks.iter().map(|k| self.get_maxPD_node(node_id, *k)).collect(),
+                    avgPD: ks.iter().map(|k| self.get_avgPD_node(node_id, *k)).collect(),
+                    min_genPD: gen_pd_size(&self.precomputed_norm_min[node_id], Ordering::Less)
+                        .map(|k| self.precomputed_norm_min[node_id][k].0),
+                    max_genPD: gen_pd_size(&self.precomputed_norm_max[node_id], Ordering::Greater)
+                        .map(|k| self.precomputed_norm_max[node_id][k].0),
+                }
+            })
+    }
+
+    /// Name of a node, or the names of the leftmost and rightmost leaves below it
+    fn clade_label(&self, node_id: usize) -> String {
+        if let Some(taxa) = self.tree.get_node_taxa(node_id) {
+            return taxa.to_string();
+        }
+        let extreme_leaf = |rightmost: bool| {
+            let mut leaf_id = node_id;
+            while !self.tree.is_leaf(leaf_id) {
+                let mut children = self.tree.get_node_children_ids(leaf_id);
+                leaf_id = match rightmost {
+                    true => children.last().unwrap(),
+                    false => children.next().unwrap(),
+                };
+            }
+            self.tree
+                .get_node_taxa(leaf_id)
+                .map(|taxa| taxa.to_string())
+                .unwrap_or_default()
+        };
+        format!("mrca({}|{})", extreme_leaf(false), extreme_leaf(true))
+    }
+
+    /// Clamps a subset size to the number of leaves in the clade below a node
+    fn clamp_num_taxa(&self, node_id: usize, num_taxa: usize) -> usize {
+        min(num_taxa, self.tree.get_cluster_size(node_id))
+    }
 }
 impl<'a,T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> TreePDMap for TreePD<'a,T,W,Z> {
 
@@ -57,13 +593,15 @@ impl<'a,T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> TreePDMap for TreePD<'a,T,W,Z> {
         self.tree
     }
 
-    fn get_minPD(
+    fn get_clade_id(
         &self,
-        num_taxa: usize,
-    ) -> TreeNodeWeight<Self::Tree>
-    {
-        self.precomputed_min[self.tree.get_root_id()][min(num_taxa, self.tree.num_taxa())]
-            .0
+        taxa: &[TreeNodeMeta<Self::Tree>],
+    ) -> Result<TreeNodeID<Self::Tree>, PDError> {
+        let leaf_ids = taxa
+            .iter()
+            .map(|taxon| self.get_taxon_id(taxon))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(mrca_id(self.tree, &leaf_ids))
     }
 
     fn get_minPD_node(
@@ -72,73 +610,55 @@ impl<'a,T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> TreePDMap for TreePD<'a,T,W,Z> {
         num_taxa: usize,
     ) -> TreeNodeWeight<Self::Tree>
     {
-        self.precomputed_min[node_id][min(num_taxa, self.tree.num_taxa())].0
+        self.precomputed_min[node_id][self.clamp_num_taxa(node_id, num_taxa)].0
     }
 
-    fn get_norm_minPD(
+    fn get_norm_minPD_node(
         &self,
+        node_id: TreeNodeID<Self::Tree>,
         num_taxa: usize,
     ) -> TreeNodeWeight<Self::Tree>
     {
-        self.precomputed_norm_min[self.tree.get_root_id()]
-            [min(num_taxa, self.tree.num_taxa())]
-        .0
+        self.precomputed_norm_min[node_id][self.clamp_num_taxa(node_id, num_taxa)].0
     }
 
-    fn get_minPD_taxa_set(
+    fn get_minPD_taxa_set_node(
         &self,
+        node_id: TreeNodeID<Self::Tree>,
         num_taxa: usize,
     ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
-        self.precomputed_min_set[self.tree.get_root_id()][num_taxa]
+        self.precomputed_min_set[node_id][self.clamp_num_taxa(node_id, num_taxa)]
             .clone()
             .into_iter()
     }
 
-    fn get_norm_minPD_taxa_set(
+    fn get_norm_minPD_taxa_set_node(
         &self,
+        node_id: TreeNodeID<Self::Tree>,
         num_taxa: usize,
     ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
-        self.precomputed_norm_min_set[self.tree.get_root_id()][num_taxa]
+        self.precomputed_norm_min_set[node_id][self.clamp_num_taxa(node_id, num_taxa)]
             .clone()
             .into_iter()
     }
 
-    fn get_min_genPD(
+    fn get_min_genPD_node(
         &self,
-    ) -> TreeNodeWeight<Self::Tree>
+        node_id: TreeNodeID<Self::Tree>,
+    ) -> Option<TreeNodeWeight<Self::Tree>>
     {
-        self.precomputed_norm_min[self.tree.get_root_id()]
-            .iter()
-            .enumerate()
-            .filter(|(x,_)| *x > 2)
-            .filter(|(_, x)| x.0 != W::zero() && x.0 != W::infinity())
-            .min_by(|(_, x), (_, y)| x.0.partial_cmp(&y.0).unwrap())
-            .unwrap()
-            .1.0
+        let num_taxa = gen_pd_size(&self.precomputed_norm_min[node_id], Ordering::Less)?;
+        Some(self.precomputed_norm_min[node_id][num_taxa].0)
     }
 
-    fn get_min_genPD_set(
+    fn get_min_genPD_set_node(
         &self,
-    ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
-        let num_taxa = self.precomputed_norm_min[self.tree.get_root_id()]
-            .iter()
-            .enumerate()
-            .filter(|x| x.0 > 2 && x.1.0 != W::zero() && x.1 .0 != W::infinity())
-            .min_by(|x, y| x.1 .0.partial_cmp(&y.1 .0).unwrap())
-            .unwrap()
-            .0;
-        self.precomputed_norm_min_set[self.tree.get_root_id()][num_taxa]
+        node_id: TreeNodeID<Self::Tree>,
+    ) -> Option<impl Iterator<Item = TreeNodeID<Self::Tree>>> {
+        let num_taxa = gen_pd_size(&self.precomputed_norm_min[node_id], Ordering::Less)?;
+        Some(self.precomputed_norm_min_set[node_id][num_taxa]
             .clone()
-            .into_iter()
-    }
-
-    fn get_maxPD(
-        &self,
-        num_taxa: usize,
-    ) -> TreeNodeWeight<Self::Tree>
-    {
-        self.precomputed_max[self.tree.get_root_id()][max(num_taxa, 1)]
-            .0
+            .into_iter())
     }
 
     fn get_maxPD_node(
@@ -147,80 +667,226 @@ impl<'a,T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> TreePDMap for TreePD<'a,T,W,Z> {
         num_taxa: usize,
     ) -> TreeNodeWeight<Self::Tree>
     {
-        self.precomputed_max[node_id][max(num_taxa, self.tree.num_taxa()-1)].0
+        self.precomputed_max[node_id][self.clamp_num_taxa(node_id, num_taxa)].0
     }
 
-    fn get_norm_maxPD(
+    fn get_norm_maxPD_node(
         &self,
+        node_id: TreeNodeID<Self::Tree>,
         num_taxa: usize,
     ) -> TreeNodeWeight<Self::Tree>
     {
-        self.precomputed_norm_max[self.tree.get_root_id()]
-            [max(num_taxa, self.tree.num_taxa()-1)]
-        .0
+        self.precomputed_norm_max[node_id][self.clamp_num_taxa(node_id, num_taxa)].0
     }
 
-    fn get_maxPD_taxa_set(
+    fn get_maxPD_taxa_set_node(
         &self,
+        node_id: TreeNodeID<Self::Tree>,
         num_taxa: usize,
     ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
-        self.precomputed_max_set[self.tree.get_root_id()][num_taxa]
+        self.precomputed_max_set[node_id][self.clamp_num_taxa(node_id, num_taxa)]
             .clone()
             .into_iter()
     }
 
-    fn get_norm_maxPD_taxa_set(
+    fn get_norm_maxPD_taxa_set_node(
         &self,
+        node_id: TreeNodeID<Self::Tree>,
         num_taxa: usize,
     ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
-        self.precomputed_norm_max_set[self.tree.get_root_id()][num_taxa]
+        self.precomputed_norm_max_set[node_id][self.clamp_num_taxa(node_id, num_taxa)]
             .clone()
             .into_iter()
     }
 
-    fn get_max_genPD(
+    fn get_max_genPD_node(
         &self,
-    ) -> TreeNodeWeight<Self::Tree>
+        node_id: TreeNodeID<Self::Tree>,
+    ) -> Option<TreeNodeWeight<Self::Tree>>
     {
-        self.precomputed_norm_max[self.tree.get_root_id()]
-            .iter()
-            .enumerate()
-            .filter(|(x,_)| *x > 2)
-            .filter(|(_, x)| x.0 != W::zero() && x.0 != W::infinity())
-            .max_by(|(_, x), (_, y)| x.0.partial_cmp(&y.0).unwrap())
-            .unwrap()
-            .1.0
+        let num_taxa = gen_pd_size(&self.precomputed_norm_max[node_id], Ordering::Greater)?;
+        Some(self.precomputed_norm_max[node_id][num_taxa].0)
     }
 
-    fn get_max_genPD_set(
+    fn get_max_genPD_set_node(
         &self,
-    ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
-        let num_taxa = self.precomputed_norm_max[self.tree.get_root_id()]
-            .iter()
-            .enumerate()
-            .filter(|x| x.0 > 2 && x.1.0 != W::zero() && x.1 .0 != W::infinity())
-            .max_by(|x, y| x.1 .0.partial_cmp(&y.1 .0).unwrap())
-            .unwrap()
-            .0;
-        self.precomputed_norm_max_set[self.tree.get_root_id()][num_taxa]
+        node_id: TreeNodeID<Self::Tree>,
+    ) -> Option<impl Iterator<Item = TreeNodeID<Self::Tree>>> {
+        let num_taxa = gen_pd_size(&self.precomputed_norm_max[node_id], Ordering::Greater)?;
+        Some(self.precomputed_norm_max_set[node_id][num_taxa]
             .clone()
-            .into_iter()
+            .into_iter())
     }
 
-    fn get_avgPD(
-            &self,
-            num_taxa: usize,
-        ) -> TreeNodeWeight<Self::Tree> {
-        self.precomputed_avg[self.tree.get_root_id()][num_taxa]
+    fn get_avgPD_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+        num_taxa: usize,
+    ) -> TreeNodeWeight<Self::Tree> {
+        self.precomputed_avg[node_id][self.clamp_num_taxa(node_id, num_taxa)]
+    }
+
+}
+
+/// Whether a candidate set (with its edge count) is preferred over the incumbent
+/// set of equal value under a tie-breaking rule. Both sets are sorted by label rank.
+fn prefers(
+    ranks: &[usize],
+    tie_break: TieBreak,
+    candidate: (u32, &[usize]),
+    incumbent: (u32, &[usize]),
+) -> bool {
+    let by_edges = match tie_break {
+        TieBreak::Lexicographic => Ordering::Equal,
+        TieBreak::FewestEdges => candidate.0.cmp(&incumbent.0),
+        TieBreak::MostEdges => incumbent.0.cmp(&candidate.0),
+    };
+    by_edges.then_with(|| {
+        candidate.1.iter().map(|x| ranks[*x]).cmp(incumbent.1.iter().map(|x| ranks[*x]))
+    }) == Ordering::Less
+}
+
+/// Whether a candidate value improves on the incumbent under `op`, deferring to
+/// `prefers` when the two are tied
+fn improves<W:EdgeWeight>(op: Ordering, val: W, best_val: W, prefers: impl FnOnce() -> bool) -> bool {
+    match is_tie(val, best_val) {
+        true => prefers(),
+        false => val.partial_cmp(&best_val) == Some(op),
     }
+}
+
+/// Whether two values are equal up to the rounding of a few sums, so that ties do not
+/// depend on the order in which edge lengths were added
+fn is_tie<W:EdgeWeight>(a: W, b: W) -> bool {
+    a == b
+        || (a.is_finite() && b.is_finite()
+            && (a - b).abs() <= a.abs().max(b.abs()) * W::epsilon() * W::from(TIE_ULPS).unwrap())
+}
+
+/// Rank of every leaf by taxon label, then NodeID. Taxa sets kept sorted by rank
+/// compare lexicographically by their sorted labels.
+fn label_ranks<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(tree: &SimpleRootedTree<T,W,Z>) -> Vec<usize> {
+    let mut ranks = vec![usize::MAX; tree.get_node_ids().max().map(|x| x + 1).unwrap_or(0)];
+    for (rank, leaf_id) in tree.get_leaf_ids().sorted_by_key(|x| (tree.get_node_taxa(*x), *x)).enumerate() {
+        ranks[leaf_id] = rank;
+    }
+    ranks
+}
 
+/// Union of two disjoint taxa sets sorted by label rank, kept sorted
+fn merge_sets(ranks: &[usize], x: &[usize], y: &[usize]) -> Vec<usize> {
+    x.iter().merge_by(y.iter(), |a, b| ranks[**a] <= ranks[**b]).copied().collect()
+}
+
+/// Maps the taxon label of every leaf to its NodeID, failing on unlabelled leaves or
+/// labels shared by several leaves
+fn taxon_index<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree: &SimpleRootedTree<T,W,Z>,
+) -> Result<HashMap<T, usize>, PDError> {
+    let mut index = HashMap::new();
+    let mut num_unlabelled = 0;
+    for leaf_id in tree.get_leaf_ids() {
+        match tree.get_node_taxa(leaf_id) {
+            Some(taxon) => {
+                if index.insert(taxon.clone(), leaf_id).is_some() {
+                    return Err(PDError::DuplicateTaxon(taxon.to_string()));
+                }
+            },
+            None => num_unlabelled += 1,
+        }
+    }
+    match num_unlabelled {
+        0 => Ok(index),
+        _ => Err(PDError::UnlabelledLeaves { num_unlabelled }),
+    }
+}
+
+/// Returns the subset size with the optimal normalized PD in a row of a normalized table,
+/// ignoring subsets of fewer than three taxa and degenerate (zero or unfilled) entries
+fn gen_pd_size<W: EdgeWeight>(row: &[(W, u32)], op: Ordering) -> Option<usize> {
+    let candidates = row
+        .iter()
+        .enumerate()
+        .filter(|(x, y)| *x > 2 && y.0 != W::zero() && y.0 != W::infinity() && y.0 != W::min_value());
+    let cmp = |x: &(usize, &(W, u32)), y: &(usize, &(W, u32))| x.1.0.partial_cmp(&y.1.0).unwrap();
+    match op {
+        Ordering::Greater => candidates.max_by(cmp),
+        _ => candidates.min_by(cmp),
+    }
+    .map(|(num_taxa, _)| num_taxa)
 }
 
 impl<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> RootedPhylogeneticDiversity for SimpleRootedTree<T,W,Z> {
 
+    fn resolve_edge_lengths(
+        &self,
+        policy: MissingLengthPolicy,
+    ) -> Result<(Vec<W>, usize), PDError> {
+        let num_ids = self.get_node_ids().max().map(|x| x + 1).unwrap_or(0);
+        let mut lengths = vec![W::zero(); num_ids];
+        let mut missing = vec![];
+        for node_id in self.get_node_ids() {
+            if node_id == self.get_root_id() {
+                continue;
+            }
+            match self.get_node(node_id).unwrap().get_weight() {
+                Some(w) => lengths[node_id] = w,
+                None => missing.push(node_id),
+            }
+        }
+        let fill = match policy {
+            MissingLengthPolicy::Error if !missing.is_empty() => {
+                return Err(PDError::MissingBranchLengths {
+                    num_missing: missing.len(),
+                });
+            }
+            MissingLengthPolicy::Error | MissingLengthPolicy::Zero => W::zero(),
+            MissingLengthPolicy::Unit => W::one(),
+            MissingLengthPolicy::Mean => {
+                let mut is_missing = vec![false; num_ids];
+                for node_id in missing.iter() {
+                    is_missing[*node_id] = true;
+                }
+                let present = self
+                    .get_node_ids()
+                    .filter(|x| *x != self.get_root_id() && !is_missing[*x])
+                    .map(|x| lengths[x])
+                    .collect_vec();
+                if present.is_empty() {
+                    return Err(PDError::NoBranchLengths);
+                }
+                let num_present = W::from(present.len()).unwrap();
+                present.into_iter().sum::<W>() / num_present
+            }
+        };
+        for node_id in missing.iter() {
+            lengths[*node_id] = fill;
+        }
+        Ok((lengths, missing.len()))
+    }
+
+    fn apply_node_weights(
+        &self,
+        edge_lengths: &mut [W],
+        policy: NodeWeightPolicy,
+    ) {
+        let sign = match policy {
+            NodeWeightPolicy::Ignore => return,
+            NodeWeightPolicy::Bonus => W::one(),
+            NodeWeightPolicy::Cost => -W::one(),
+        };
+        for node_id in self.get_node_ids().filter(|x| *x != self.get_root_id()) {
+            if let Some(weight) = self.get_node(node_id).unwrap().get_zeta() {
+                edge_lengths[node_id] = edge_lengths[node_id] + sign * W::from(weight).unwrap();
+            }
+        }
+    }
+
     fn compute_dp_table(
         &self,
         op: Ordering,
+        edge_lengths: &[W],
+        tie_break: TieBreak,
     ) -> (
         Vec<Vec<(W, u32)>>,
         Vec<Vec<Vec<TreeNodeID<Self>>>>,
@@ -228,17 +894,14 @@ impl<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> RootedPhylogeneticDiversity for Simpl
         Vec<Vec<Vec<TreeNodeID<Self>>>>,
     )
     {
-        let op_fn = match op{
-            Ordering::Greater => PartialOrd::ge,
-            _ => PartialOrd::le,
-        };
-
         let start_val = match op{
             Ordering::Greater => W::min_value(),
             _ => W::infinity(),
         };
 
         let num_leaves = self.get_leaves().len();
+        let edge_counts = edge_counts(self, edge_lengths);
+        let ranks = label_ranks(self);
         let mut delta_bar: Vec<Vec<(W, u32)>> =
             vec![vec![(start_val, 0_u32); num_leaves + 1]; self.get_nodes().len()];
         let mut delta_bar_sets: Vec<Vec<Vec<usize>>> =
@@ -259,70 +922,92 @@ impl<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> RootedPhylogeneticDiversity for Simpl
             }
         }
 
+        // internal nodes below a zero-length edge (such as those added when resolving
+        // polytomies) only split a polytomy, so the nodes above them merge the children
+        // of the whole polytomy, in the order of their smallest taxon label
+        let root_id = self.get_root_id();
+        let is_split = |node_id: usize| {
+            node_id != root_id
+                && !self.is_leaf(node_id)
+                && edge_lengths[node_id] == W::zero()
+                && edge_counts[node_id] == 0
+        };
+        let mut first_label: Vec<Option<&T>> = vec![None; edge_lengths.len()];
         for node_id in self.postord_ids(self.get_root_id()) {
-            if !self.is_leaf(node_id) {
-                for i in 1..(min(num_leaves, self.get_cluster_size(node_id)) + 1)
-                {
-                    // Min pd
-                    let mut min_bar = match op{
-                        Ordering::Greater => W::min_value(),
-                        _ => W::max_value(),
-                    };
-                    let mut min_hat = match op{
-                        Ordering::Greater => W::min_value(),
-                        _ => W::max_value(),
-                    };
-                    // min pd set
-                    let mut min_bar_set: Vec<usize> = vec![];
-                    // min norm pd set
-                    let mut min_hat_set: Vec<usize> = vec![];
-                    // min pd edge_count
+            first_label[node_id] = match self.is_leaf(node_id) {
+                true => self.get_node_taxa(node_id),
+                false => self.get_node_children_ids(node_id).filter_map(|x| first_label[x]).min(),
+            };
+        }
+
+        for node_id in self.postord_ids(self.get_root_id()) {
+            if self.is_leaf(node_id) {
+                continue;
+            }
+            let mut node_children = self.get_node_children_ids(node_id).collect_vec();
+            if !is_split(node_id) {
+                while let Some(i) = node_children.iter().position(|x| is_split(*x)) {
+                    let split_id = node_children.swap_remove(i);
+                    node_children.extend(self.get_node_children_ids(split_id));
+                }
+                node_children.sort_by_key(|x| (first_label[*x], *x));
+            }
+            // optimal sets of every size among the children merged so far, with their
+            // edge counts; normalized PD is optimized over the unions formed by the
+            // last merge
+            let mut merged: Vec<(W, u32, Vec<usize>)> = vec![(W::zero(), 0_u32, vec![])];
+            for (j, x) in node_children.iter().copied().enumerate() {
+                let is_last = j + 1 == node_children.len();
+                let merged_size = merged.len() - 1;
+                let x_cluster_size = self.get_cluster_size(x);
+                let mut next = vec![(W::zero(), 0_u32, vec![])];
+                for i in 1..(merged_size + x_cluster_size + 1) {
+                    let mut min_bar = start_val;
+                    let mut min_hat = start_val;
                     let mut min_e_bar = 0_u32;
-                    // min norm pd edge count
                     let mut min_e_hat = 0_u32;
-                    let node_children = self.get_node_children_ids(node_id).collect_vec();
-                    let x = node_children[0];
-                    let y = node_children[1];
-                    let x_cluster_size = self.get_cluster_size(x);
-                    let y_cluster_size = self.get_cluster_size(y);
-                    for (l,r) in binary_splits(i, x_cluster_size, y_cluster_size){
-                        let discount_l = self.get_node(y).unwrap().get_weight().is_some() as u32;
-                        let discount_r = self.get_node(x).unwrap().get_weight().is_some() as u32;
-                        let val_bar: W = delta_bar[y][r].0
-                            + (self.get_node(y).unwrap().get_weight().unwrap_or(W::zero())*W::from(min(r, 1)).unwrap())
-                            + delta_bar[x][l].0
-                            + (self.get_node(x).unwrap().get_weight().unwrap_or(W::zero())
-                                * W::from(min(l, 1)).unwrap());
-                        let mut e = 0_u32;
-                        let mut set: Vec<usize>;
-                        if l == 0 {
-                            e += delta_bar[y][r].1;
-                            set = delta_bar_sets[y][r].clone();
-                        } else if r == 0 {
-                            e += delta_bar[x][l].1;
-                            set = delta_bar_sets[x][l].clone();
-                        } else {
-                            e += delta_bar[x][l].1 + delta_bar[y][r].1 + discount_r +discount_l;
-                            set = delta_bar_sets[x][l].clone();
-                            set.extend(delta_bar_sets[y][r].clone());
-                        }
-                        let val_hat = val_bar / W::from(e).unwrap();
-                        if op_fn(&val_bar, &min_bar) {
+                    // sizes of the part from the merged children in the optimal sets,
+                    // which are only built when needed to break a tie
+                    let mut min_bar_l: Option<usize> = None;
+                    let mut min_hat_l: Option<usize> = None;
+                    let set_of = |l: usize| merge_sets(&ranks, &merged[l].2, &delta_bar_sets[x][i - l]);
+                    let lower = i.saturating_sub(x_cluster_size);
+                    for (l, (merged_val, merged_e, _)) in
+                        merged.iter().enumerate().take(min(i, merged_size) + 1).skip(lower)
+                    {
+                        let r = i - l;
+                        // only a child with selected taxa spans the edge into it
+                        let val_bar = *merged_val
+                            + (delta_bar[x][r].0 + edge_lengths[x] * W::from(min(r, 1)).unwrap());
+                        let e = merged_e + delta_bar[x][r].1 + edge_counts[x] * min(r, 1) as u32;
+                        let prefers_l = |incumbent: Option<usize>, incumbent_e: u32| {
+                            incumbent.is_none_or(|m| prefers(&ranks, tie_break, (e, &set_of(l)), (incumbent_e, &set_of(m))))
+                        };
+                        if improves(op, val_bar, min_bar, || prefers_l(min_bar_l, min_e_bar)) {
                             min_bar = val_bar;
                             min_e_bar = e;
-                            min_bar_set = set.clone();
+                            min_bar_l = Some(l);
                         }
-                        if op_fn(&val_hat, &min_hat) {
+                        let val_hat = val_bar / W::from(e).unwrap();
+                        if is_last && improves(op, val_hat, min_hat, || prefers_l(min_hat_l, min_e_hat)) {
                             min_hat = val_hat;
                             min_e_hat = e;
-                            min_hat_set = set;
+                            min_hat_l = Some(l);
                         }
                     }
-                    delta_bar[node_id][i] = (min_bar, min_e_bar);
-                    delta_hat[node_id][i] = (min_hat, min_e_hat);
-                    delta_bar_sets[node_id][i] = min_bar_set;
-                    delta_hat_sets[node_id][i] = min_hat_set;
+                    let min_bar_set = min_bar_l.map(set_of).unwrap_or_default();
+                    let min_hat_set = min_hat_l.map(set_of).unwrap_or_default();
+                    next.push((min_bar, min_e_bar, min_bar_set));
+                    if is_last {
+                        delta_hat[node_id][i] = (min_hat, min_e_hat);
+                        delta_hat_sets[node_id][i] = min_hat_set;
+                    }
                 }
+                merged = next;
+            }
+            for (i, (val, e, set)) in merged.into_iter().enumerate().skip(1) {
+                delta_bar[node_id][i] = (val, e);
+                delta_bar_sets[node_id][i] = set;
             }
         }
 
@@ -331,43 +1016,423 @@ impl<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight> RootedPhylogeneticDiversity for Simpl
 
     fn compute_avg(
             &self,
+            edge_lengths: &[W],
         ) -> Vec<Vec<TreeNodeWeight<Self>>> {
-        let num_leaves = self.num_taxa();
+        let num_leaves = self.get_leaves().len();
         let pascal = pascal_triangle(num_leaves as u32);
         let mut alpha = vec![vec![W::zero(); num_leaves + 1]; self.get_nodes().len()];
         let mut beta = vec![vec![W::zero(); num_leaves + 1]; self.get_nodes().len()];
 
         for node_id in self.postord_ids(self.get_root_id()){
-            match self.is_leaf(node_id) {
-                true => {
-                    alpha[node_id][1] = W::zero();
-                    beta[node_id][1] = W::zero();
-                },
-                _ => {
-                    let node_children = self.get_node_children_ids(node_id).collect_vec();
-                    let x = node_children[0];
-                    let y = node_children[1];
-                    let x_cluster_size = self.get_cluster_size(x);
-                    let y_cluster_size = self.get_cluster_size(y);
-                    let w_x = self.get_node(x).unwrap().get_weight().unwrap_or(W::zero());
-                    let w_y = self.get_node(y).unwrap().get_weight().unwrap_or(W::zero());
-                    for i in 1..(min(num_leaves, self.get_cluster_size(node_id)) + 1){
-                        let s_x = beta[x][i] + W::from(pascal[x_cluster_size][i]).unwrap()*w_x;
-                        let s_y = beta[y][i] + W::from(pascal[y_cluster_size][i]).unwrap()*w_y;
-                        let mut s = s_x + s_y;
-                        for r in max(1,i as isize-y_cluster_size as isize)..min(x_cluster_size as isize,i as isize-1)+1{
-                            let l = (i as isize-r) as usize;
-                            s = s + W::from(pascal[y_cluster_size][l]).unwrap()*beta[x][r as usize]
-                                + W::from(pascal[x_cluster_size][r as usize]).unwrap()*beta[y][l]
-                                + W::from(pascal[y_cluster_size][l]).unwrap()*W::from(pascal[x_cluster_size][r as usize]).unwrap()*(w_x+w_y);
-                        }
-                        beta[node_id][i] = s;
-                        alpha[node_id][i] = s/W::from(pascal[x_cluster_size+y_cluster_size][i]).unwrap();
+            if self.is_leaf(node_id) {
+                alpha[node_id][1] = W::zero();
+                beta[node_id][1] = W::zero();
+                continue;
+            }
+            // total PD of the sets of every size among the children merged so far
+            let mut merged = vec![W::zero()];
+            for x in self.get_node_children_ids(node_id) {
+                let merged_size = merged.len() - 1;
+                let x_cluster_size = self.get_cluster_size(x);
+                let w_x = edge_lengths[x];
+                let mut next = vec![W::zero(); merged_size + x_cluster_size + 1];
+                for (l, merged_pd) in merged.iter().enumerate() {
+                    for r in 0..(x_cluster_size + 1) {
+                        // a set from the child spans the edge into it unless empty
+                        let s_x = match r {
+                            0 => W::zero(),
+                            _ => beta[x][r] + W::from(pascal[x_cluster_size][r]).unwrap()*w_x,
+                        };
+                        next[l + r] = next[l + r]
+                            + W::from(pascal[x_cluster_size][r]).unwrap()*(*merged_pd)
+                            + W::from(pascal[merged_size][l]).unwrap()*s_x;
                     }
-                },
-            };
+                }
+                merged = next;
+            }
+            let cluster_size = merged.len() - 1;
+            for (i, s) in merged.into_iter().enumerate().skip(1) {
+                beta[node_id][i] = s;
+                alpha[node_id][i] = s/W::from(pascal[cluster_size][i]).unwrap();
+            }
         }
         alpha
     }
 
+    fn compute_maxPD_ranking(
+        &self,
+        edge_lengths: &[W],
+    ) -> Vec<(usize, W)> {
+        let root_id = self.get_root_id();
+        // length of the longest path down from every node, and the leaf it ends at
+        let mut height = vec![W::zero(); edge_lengths.len()];
+        let mut deepest_leaf = (0..edge_lengths.len()).collect_vec();
+        let by_gain_then_label = |(x_leaf, x_gain): &(usize, W), (y_leaf, y_gain): &(usize, W)| {
+            y_gain
+                .partial_cmp(x_gain)
+                .unwrap()
+                .then_with(|| (self.get_node_taxa(*x_leaf), x_leaf).cmp(&(self.get_node_taxa(*y_leaf), y_leaf)))
+        };
+        for node_id in self.postord_ids(root_id) {
+            if let Some((leaf_id, path_length)) = self
+                .get_node_children_ids(node_id)
+                .map(|child_id| (deepest_leaf[child_id], edge_lengths[child_id] + height[child_id]))
+                .min_by(by_gain_then_label)
+            {
+                height[node_id] = path_length;
+                deepest_leaf[node_id] = leaf_id;
+            }
+        }
+        // every leaf gains the path from itself up to where it meets a longer path
+        let mut gains = vec![(deepest_leaf[root_id], height[root_id])];
+        for node_id in self.get_node_ids() {
+            if let Some(parent_id) = self.get_node_parent_id(node_id) {
+                if deepest_leaf[parent_id] != deepest_leaf[node_id] {
+                    gains.push((deepest_leaf[node_id], edge_lengths[node_id] + height[node_id]));
+                }
+            }
+        }
+        gains.sort_by(by_gain_then_label);
+        gains
+    }
+
+    fn compute_complement(
+        &self,
+        edge_lengths: &[W],
+        protected: &[usize],
+        tie_break: TieBreak,
+    ) -> Vec<(W, Vec<usize>)> {
+        complement_dp(self, Ordering::Greater, edge_lengths, protected, |_| true, tie_break)
+    }
+
+    fn compute_valued_maxPD(
+        &self,
+        edge_lengths: &[W],
+        leaf_values: &[W],
+        tie_break: TieBreak,
+    ) -> Vec<(W, Vec<usize>)> {
+        additive_dp(self, Ordering::Greater, edge_lengths, |leaf_id| Some(leaf_values[leaf_id]), tie_break)
+    }
+
+    fn compute_robust_maxPD(
+        &self,
+        edge_lengths: &[W],
+        num_taxa: usize,
+        removals: usize,
+        tie_break: TieBreak,
+    ) -> (W, Vec<usize>) {
+        // for every number of selected taxa, candidate sets with the PD remaining after
+        // each number of removals from 0 to `removals`
+        let mut tables: Vec<Vec<Vec<(Vec<W>, Vec<usize>)>>> = vec![vec![]; edge_lengths.len()];
+        let empty = (vec![W::zero(); removals + 1], vec![]);
+        let ranks = label_ranks(self);
+        for node_id in self.postord_ids(self.get_root_id()) {
+            if self.is_leaf(node_id) {
+                tables[node_id] = vec![vec![empty.clone()], vec![(vec![W::zero(); removals + 1], vec![node_id])]];
+                tables[node_id].truncate(num_taxa + 1);
+                continue;
+            }
+            let mut table = vec![vec![empty.clone()]];
+            for child_id in self.get_node_children_ids(node_id).collect_vec() {
+                let child_table = std::mem::take(&mut tables[child_id]);
+                let mut merged: Vec<Vec<(Vec<W>, Vec<usize>)>> =
+                    vec![vec![]; min(table.len() + child_table.len() - 1, num_taxa + 1)];
+                for (l, front_l) in table.iter().enumerate() {
+                    for (m, front_m) in child_table.iter().enumerate().take(num_taxa + 1 - l) {
+                        for (remaining_l, set_l) in front_l.iter() {
+                            for (remaining_m, set_m) in front_m.iter() {
+                                // the edge into the child remains while a child taxon survives
+                                let with_edge = |j: usize| match m > j {
+                                    true => remaining_m[j] + edge_lengths[child_id],
+                                    false => remaining_m[j],
+                                };
+                                let remaining = (0..removals + 1)
+                                    .map(|j| {
+                                        (j.saturating_sub(m)..min(j, l) + 1)
+                                            .map(|j_l| remaining_l[j_l] + with_edge(j - j_l))
+                                            .reduce(W::min)
+                                            .unwrap_or(W::zero())
+                                    })
+                                    .collect_vec();
+                                merged[l + m].push((remaining, merge_sets(&ranks, set_l, set_m)));
+                            }
+                        }
+                    }
+                }
+                table = merged
+                    .into_iter()
+                    .map(|front| robust_front(&ranks, front, removals, tie_break))
+                    .collect();
+            }
+            tables[node_id] = table;
+        }
+        let root_table = tables.swap_remove(self.get_root_id());
+        root_table
+            .into_iter()
+            .nth(num_taxa)
+            .unwrap_or_default()
+            .into_iter()
+            .reduce(|best, candidate| {
+                let improves = improves(Ordering::Greater, candidate.0[removals], best.0[removals], || {
+                    prefers(&ranks, tie_break, (0, &candidate.1), (0, &best.1))
+                });
+                match improves {
+                    true => candidate,
+                    false => best,
+                }
+            })
+            .map(|(remaining, set)| (remaining[removals], set))
+            .unwrap_or((W::zero(), vec![]))
+    }
+
+    fn compute_pareto_front(
+        &self,
+        op: Ordering,
+        edge_lengths: &[W],
+        tie_break: TieBreak,
+    ) -> Vec<Vec<(W, u32, Vec<usize>)>> {
+        let num_leaves = self.get_leaves().len();
+        let ranks = label_ranks(self);
+        let edge_counts = edge_counts(self, edge_lengths);
+        let mut fronts: Vec<Vec<Vec<(W, u32, Vec<usize>)>>> =
+            vec![vec![vec![]; num_leaves + 1]; self.get_nodes().len()];
+
+        for node_id in self.postord_ids(self.get_root_id()) {
+            fronts[node_id][0] = vec![(W::zero(), 0_u32, vec![])];
+            if self.is_leaf(node_id) {
+                fronts[node_id][1] = vec![(W::zero(), 0_u32, vec![node_id])];
+                continue;
+            }
+            // fronts of every size among the children merged so far; PD and edge
+            // counts add up over disjoint clades, so dominated entries are dropped
+            // after every merge
+            let mut merged: Vec<Vec<(W, u32, Vec<usize>)>> = vec![vec![(W::zero(), 0_u32, vec![])]];
+            for x in self.get_node_children_ids(node_id).collect_vec() {
+                let x_fronts = std::mem::take(&mut fronts[x]);
+                let x_cluster_size = self.get_cluster_size(x);
+                // best PD (and set) for every size and edge count
+                let mut candidates: Vec<HashMap<u32, (W, Vec<usize>)>> =
+                    vec![HashMap::new(); merged.len() + x_cluster_size];
+                for (l, front_l) in merged.iter().enumerate() {
+                    for (r, front_r) in x_fronts.iter().enumerate().take(x_cluster_size + 1) {
+                        for (val_l, e_l, set_l) in front_l.iter() {
+                            for (val_r, e_r, set_r) in front_r.iter() {
+                                // only a child with selected taxa spans the edge into it
+                                let val = *val_l + (*val_r + edge_lengths[x] * W::from(min(r, 1)).unwrap());
+                                let e = e_l + e_r + edge_counts[x] * min(r, 1) as u32;
+                                let set = || merge_sets(&ranks, set_l, set_r);
+                                let replace = match candidates[l + r].get(&e) {
+                                    None => true,
+                                    Some((best_val, best_set)) => {
+                                        improves(op, val, *best_val, || prefers(&ranks, tie_break, (e, &set()), (e, best_set)))
+                                    }
+                                };
+                                if replace {
+                                    candidates[l + r].insert(e, (val, set()));
+                                }
+                            }
+                        }
+                    }
+                }
+                merged = candidates.into_iter().map(|x| pareto_sweep(x, op)).collect();
+            }
+            for (i, front) in merged.into_iter().enumerate().skip(1) {
+                fronts[node_id][i] = front;
+            }
+        }
+        fronts.swap_remove(self.get_root_id())
+    }
+
+}
+
+/// Computes, for every number of additional taxa, the largest (or smallest, for
+/// `Ordering::Less`) gain in PD over a protected set of leaves and the taxa achieving
+/// it, choosing only among the unprotected leaves that are selectable
+fn complement_dp<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree: &SimpleRootedTree<T,W,Z>,
+    op: Ordering,
+    edge_lengths: &[W],
+    protected: &[usize],
+    is_selectable: impl Fn(usize) -> bool,
+    tie_break: TieBreak,
+) -> Vec<(W, Vec<usize>)> {
+    let mut is_protected = vec![false; edge_lengths.len()];
+    protected.iter().for_each(|leaf_id| is_protected[*leaf_id] = true);
+    // edges above a protected leaf are already paid for
+    let mut covered = is_protected.clone();
+    let mut free_lengths = edge_lengths.to_vec();
+    for node_id in tree.postord_ids(tree.get_root_id()) {
+        covered[node_id] |= tree.get_node_children_ids(node_id).any(|child_id| covered[child_id]);
+        if covered[node_id] {
+            free_lengths[node_id] = W::zero();
+        }
+    }
+    additive_dp(
+        tree,
+        op,
+        &free_lengths,
+        |leaf_id| (!is_protected[leaf_id] && is_selectable(leaf_id)).then_some(W::zero()),
+        tie_break,
+    )
+}
+
+/// Computes, for every number of taxa, the largest (or smallest, for `Ordering::Less`)
+/// PD plus the base values of the selected leaves, and the taxa achieving it. Leaves
+/// without a base value are never selected.
+fn additive_dp<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree: &SimpleRootedTree<T,W,Z>,
+    op: Ordering,
+    edge_lengths: &[W],
+    leaf_base: impl Fn(usize) -> Option<W>,
+    tie_break: TieBreak,
+) -> Vec<(W, Vec<usize>)> {
+    clade_constrained_dp(tree, op, edge_lengths, leaf_base, |_, _| true, tie_break)
+        .into_iter()
+        .map(Option::unwrap)
+        .collect()
+}
+
+/// Computes, for every number of taxa, the largest (or smallest, for `Ordering::Less`)
+/// PD plus the base values of the selected leaves among sets with an allowed number of
+/// taxa in every clade, and the taxa achieving it, or None if no such set exists.
+/// `is_allowed(node_id, count)` tells whether `count` taxa may be selected in the clade
+/// below a node. Leaves without a base value are never selected. Children are merged
+/// one at a time, so multifurcations need no resolution.
+fn clade_constrained_dp<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree: &SimpleRootedTree<T,W,Z>,
+    op: Ordering,
+    edge_lengths: &[W],
+    leaf_base: impl Fn(usize) -> Option<W>,
+    is_allowed: impl Fn(usize, usize) -> bool,
+    tie_break: TieBreak,
+) -> Vec<Option<(W, Vec<usize>)>> {
+    let mut tables: Vec<Vec<Option<(W, Vec<usize>)>>> = vec![vec![]; edge_lengths.len()];
+    let ranks = label_ranks(tree);
+    for node_id in tree.postord_ids(tree.get_root_id()) {
+        let mut table = vec![Some((W::zero(), vec![]))];
+        if tree.is_leaf(node_id) {
+            if let Some(base) = leaf_base(node_id) {
+                table.push(Some((base, vec![node_id])));
+            }
+        }
+        for child_id in tree.get_node_children_ids(node_id).collect_vec() {
+            let child_table = std::mem::take(&mut tables[child_id]);
+            let w = edge_lengths[child_id];
+            let mut merged: Vec<Option<(W, Vec<usize>)>> = vec![None; table.len() + child_table.len() - 1];
+            for (l, (val_l, set_l)) in table.iter().enumerate().filter_map(|(l, x)| Some((l, x.as_ref()?))) {
+                for (r, (val_r, set_r)) in child_table.iter().enumerate().filter_map(|(r, x)| Some((r, x.as_ref()?))) {
+                    let val = *val_l + (*val_r + w * W::from(min(r, 1)).unwrap());
+                    let set = || merge_sets(&ranks, set_l, set_r);
+                    let replace = match &merged[l + r] {
+                        None => true,
+                        Some((best_val, best_set)) => {
+                            improves(op, val, *best_val, || prefers(&ranks, tie_break, (0, &set()), (0, best_set)))
+                        }
+                    };
+                    if replace {
+                        merged[l + r] = Some((val, set()));
+                    }
+                }
+            }
+            table = merged;
+        }
+        for (count, entry) in table.iter_mut().enumerate() {
+            if !is_allowed(node_id, count) {
+                *entry = None;
+            }
+        }
+        tables[node_id] = table;
+    }
+    tables.swap_remove(tree.get_root_id())
+}
+
+/// Keeps the candidate sets whose vectors of remaining PD are not dominated by another
+/// candidate, truncated to the best worst-case PD beyond the exact number of removals
+fn robust_front<W:EdgeWeight>(
+    ranks: &[usize],
+    candidates: Vec<(Vec<W>, Vec<usize>)>,
+    removals: usize,
+    tie_break: TieBreak,
+) -> Vec<(Vec<W>, Vec<usize>)> {
+    let dominates = |x: &[W], y: &[W]| x.iter().zip(y.iter()).all(|(a, b)| a >= b);
+    let mut front: Vec<(Vec<W>, Vec<usize>)> = vec![];
+    for (remaining, set) in candidates {
+        match front.iter().position(|(other, _)| dominates(other, &remaining)) {
+            Some(pos) if !front[pos].0.iter().zip(remaining.iter()).all(|(x, y)| is_tie(*x, *y)) => continue,
+            Some(pos) => {
+                if prefers(ranks, tie_break, (0, &set), (0, &front[pos].1)) {
+                    front[pos] = (remaining, set);
+                }
+            },
+            None => {
+                front.retain(|(other, _)| !dominates(&remaining, other));
+                front.push((remaining, set));
+            },
+        }
+    }
+    if removals > ROBUST_EXACT_MAX_REMOVALS && front.len() > ROBUST_BEAM_WIDTH {
+        front.sort_by(|x, y| y.0.iter().rev().partial_cmp(x.0.iter().rev()).unwrap());
+        front.truncate(ROBUST_BEAM_WIDTH);
+    }
+    front
+}
+
+/// Keeps the non-dominated (PD, edge count) entries, sorted by PD with the best last
+fn pareto_sweep<W:EdgeWeight>(
+    candidates: HashMap<u32, (W, Vec<usize>)>,
+    op: Ordering,
+) -> Vec<(W, u32, Vec<usize>)> {
+    // visit from the most favourable edge count, keeping entries that improve PD
+    let mut entries = candidates
+        .into_iter()
+        .map(|(e, (val, set))| (val, e, set))
+        .sorted_by_key(|entry| entry.1)
+        .collect_vec();
+    if op == Ordering::Less {
+        entries.reverse();
+    }
+    let mut front: Vec<(W, u32, Vec<usize>)> = vec![];
+    for entry in entries {
+        let improves = match front.last() {
+            None => true,
+            Some(best) => match op {
+                Ordering::Greater => entry.0 > best.0,
+                _ => entry.0 < best.0,
+            },
+        };
+        if improves {
+            front.push(entry);
+        }
+    }
+    front
+}
+
+/// Number of edges contributed towards normalization by the edge into each node.
+/// Only positive-length edges are counted, and a path through unary nodes counts
+/// as a single edge, attributed to its topmost edge.
+fn edge_counts<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree: &SimpleRootedTree<T,W,Z>,
+    edge_lengths: &[W],
+) -> Vec<u32> {
+    let root_id = tree.get_root_id();
+    let is_chained = |node_id: usize| {
+        node_id != root_id && tree.get_node(node_id).unwrap().num_children() == 1
+    };
+    // whether any edge on the unary path starting at a node has positive length
+    let mut chain_positive = vec![false; edge_lengths.len()];
+    for node_id in tree.postord_ids(root_id) {
+        chain_positive[node_id] = edge_lengths[node_id] > W::zero()
+            || (is_chained(node_id)
+                && chain_positive[tree.get_node_children_ids(node_id).next().unwrap()]);
+    }
+    let mut counts = vec![0_u32; edge_lengths.len()];
+    for node_id in tree.get_node_ids() {
+        match tree.get_node_parent_id(node_id) {
+            Some(parent_id) if !is_chained(parent_id) => {
+                counts[node_id] = chain_positive[node_id] as u32;
+            },
+            _ => {},
+        }
+    }
+    counts
 }
@@ -0,0 +1,226 @@
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+use phylo::prelude::*;
+
+use super::error::PDError;
+use super::phylogenetic_diversity::{RootedPhylogeneticDiversity, TreePDMap};
+use super::{complement_dp, improves, TreePD};
+
+/// Pairs of taxa that cannot be selected together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictGraph {
+    /// NodeIDs of the leaves every leaf conflicts with, indexed by NodeID
+    conflicts: Vec<Vec<usize>>,
+}
+
+/// A conflict-free taxa set with optimal PD
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictFreeSelection<W> {
+    /// PD of the taxa set
+    pub pd: W,
+    /// NodeIDs of the taxa in the set
+    pub taxa: Vec<usize>,
+    /// Whether the set is proven optimal. This is false when the time limit was
+    /// reached, in which case the set is the best one found by then.
+    pub exact: bool,
+}
+
+impl ConflictGraph {
+    /// Builds the conflict graph from pairs of taxon labels
+    pub fn from_edges<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        tree_pd: &TreePD<'_,T,W,Z>,
+        edges: &[(T, T)],
+    ) -> Result<Self, PDError> {
+        let mut conflicts = vec![vec![]; tree_pd.edge_lengths.len()];
+        for (taxon_a, taxon_b) in edges {
+            let (leaf_a, leaf_b) = (tree_pd.get_taxon_id(taxon_a)?, tree_pd.get_taxon_id(taxon_b)?);
+            conflicts[leaf_a].push(leaf_b);
+            conflicts[leaf_b].push(leaf_a);
+        }
+        Ok(ConflictGraph { conflicts })
+    }
+
+    /// Returns true if no two taxa in the set conflict
+    pub fn is_conflict_free(&self, taxa: &[usize]) -> bool {
+        taxa.iter().all(|leaf_id| self.conflicts[*leaf_id].iter().all(|x| !taxa.contains(x)))
+    }
+
+    /// Returns a conflict-free set of a number of taxa with the largest (for
+    /// `Ordering::Greater`) or smallest (for `Ordering::Less`) PD, or None if no such
+    /// set was found.
+    ///
+    /// The set is found by branch-and-bound, starting from the greedy set. If the greedy
+    /// set attains the unconstrained optimum from the PD tables it is returned right
+    /// away. Otherwise every partial set is bounded by the best PD any completion could
+    /// reach while ignoring conflicts, computed with the complement DP over the
+    /// undecided taxa, and selecting a taxon leaves out the taxa it conflicts with. When
+    /// `time_limit` is reached, the best set found so far is returned and marked as not
+    /// exact.
+    pub fn get_conflict_free_PD<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        op: Ordering,
+        num_taxa: usize,
+        time_limit: Duration,
+    ) -> Option<ConflictFreeSelection<W>> {
+        let deadline = Instant::now() + time_limit;
+        if num_taxa > tree_pd.tree.get_leaf_ids().count() {
+            return None;
+        }
+        let best = self.get_greedy_conflict_free_PD(tree_pd, op, num_taxa);
+        let unconstrained = match op {
+            Ordering::Greater => tree_pd.get_maxPD(num_taxa),
+            _ => tree_pd.get_minPD(num_taxa),
+        };
+        if let Some((pd, taxa)) = &best {
+            if *pd == unconstrained {
+                return Some(ConflictFreeSelection { pd: *pd, taxa: taxa.clone(), exact: true });
+            }
+        }
+        // decide the taxa adding the most PD first when maximizing, and last when minimizing
+        let mut order = tree_pd
+            .tree
+            .compute_maxPD_ranking(&tree_pd.edge_lengths)
+            .into_iter()
+            .map(|(leaf_id, _)| leaf_id)
+            .collect::<Vec<_>>();
+        if op == Ordering::Less {
+            order.reverse();
+        }
+        let mut search = Search {
+            tree_pd,
+            graph: self,
+            op,
+            order,
+            num_taxa,
+            chosen: vec![],
+            excluded: vec![0; tree_pd.edge_lengths.len()],
+            best,
+            deadline,
+        };
+        let exact = search.branch(0);
+        search.best.map(|(pd, taxa)| ConflictFreeSelection { pd, taxa, exact })
+    }
+
+    /// Returns a conflict-free set of a number of taxa found greedily, or None if the
+    /// heuristic gets stuck. Taxa are added one at a time, each time choosing the taxon
+    /// with the largest (or smallest) gain in PD among those not conflicting with the
+    /// taxa already chosen.
+    pub fn get_greedy_conflict_free_PD<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        op: Ordering,
+        num_taxa: usize,
+    ) -> Option<(W, Vec<usize>)> {
+        let tree = tree_pd.tree;
+        let mut covered = vec![false; tree_pd.edge_lengths.len()];
+        let mut blocked = vec![false; tree_pd.edge_lengths.len()];
+        let mut chosen: Vec<usize> = vec![];
+        let mut pd = W::zero();
+        while chosen.len() < num_taxa {
+            let mut best: Option<(W, usize)> = None;
+            for leaf_id in tree.get_leaf_ids().filter(|x| !covered[*x] && !blocked[*x]) {
+                let gain = std::iter::successors(Some(leaf_id), |x| tree.get_node_parent_id(*x))
+                    .take_while(|x| !covered[*x])
+                    .map(|x| tree_pd.edge_lengths[x])
+                    .sum::<W>();
+                let replace = match best {
+                    None => true,
+                    Some((best_gain, best_id)) => {
+                        improves(op, gain, best_gain, || tree_pd.prefers_set(&[leaf_id], &[best_id]))
+                    }
+                };
+                if replace {
+                    best = Some((gain, leaf_id));
+                }
+            }
+            let (gain, leaf_id) = best?;
+            pd = pd + gain;
+            chosen.push(leaf_id);
+            self.conflicts[leaf_id].iter().for_each(|x| blocked[*x] = true);
+            let mut node_id = Some(leaf_id);
+            while let Some(x) = node_id.filter(|x| !covered[*x]) {
+                covered[x] = true;
+                node_id = tree.get_node_parent_id(x);
+            }
+        }
+        Some((pd, chosen))
+    }
+}
+
+/// State of the branch-and-bound search for a conflict-free set with optimal PD
+struct Search<'a, 'b, T:NodeTaxa, W:EdgeWeight, Z:NodeWeight> {
+    tree_pd: &'a TreePD<'b,T,W,Z>,
+    graph: &'a ConflictGraph,
+    op: Ordering,
+    /// Order in which taxa are decided
+    order: Vec<usize>,
+    num_taxa: usize,
+    chosen: Vec<usize>,
+    /// Number of reasons every taxon is left out: being decided against, or conflicting
+    /// with chosen taxa
+    excluded: Vec<u32>,
+    best: Option<(W, Vec<usize>)>,
+    deadline: Instant,
+}
+
+impl<T:NodeTaxa, W:EdgeWeight, Z:NodeWeight> Search<'_, '_, T, W, Z> {
+    /// Explores all sets extending the chosen taxa with taxa from `order[depth..]`.
+    /// Returns false if the time limit was reached.
+    fn branch(&mut self, mut depth: usize) -> bool {
+        if Instant::now() >= self.deadline {
+            return false;
+        }
+        let pd = self.tree_pd.taxa_set_pd(&self.chosen);
+        if self.chosen.len() == self.num_taxa {
+            if self.improves(pd) {
+                self.best = Some((pd, self.chosen.clone()));
+            }
+            return true;
+        }
+        let gains = complement_dp(
+            self.tree_pd.tree,
+            self.op,
+            &self.tree_pd.edge_lengths,
+            &self.chosen,
+            |leaf_id| self.excluded[leaf_id] == 0,
+            self.tree_pd.options.tie_break,
+        );
+        // prune when the best set so far beats every completion
+        let bound = match gains.get(self.num_taxa - self.chosen.len()) {
+            Some((gain, _)) => pd + *gain,
+            None => return true,
+        };
+        if self.best.as_ref().is_some_and(|(best_pd, _)| best_pd.partial_cmp(&bound) == Some(self.op)) {
+            return true;
+        }
+        // some taxon is undecided, since the bound found enough of them
+        while self.excluded[self.order[depth]] > 0 {
+            depth += 1;
+        }
+        let leaf_id = self.order[depth];
+        self.chosen.push(leaf_id);
+        self.graph.conflicts[leaf_id].iter().for_each(|x| self.excluded[*x] += 1);
+        let complete = self.branch(depth + 1);
+        self.graph.conflicts[leaf_id].iter().for_each(|x| self.excluded[*x] -= 1);
+        self.chosen.pop();
+        if !complete {
+            return false;
+        }
+        self.excluded[leaf_id] += 1;
+        let complete = self.branch(depth + 1);
+        self.excluded[leaf_id] -= 1;
+        complete
+    }
+
+    /// Returns true if the chosen set with PD `pd` is better than the best set so far
+    fn improves(&self, pd: W) -> bool {
+        match &self.best {
+            None => true,
+            Some((best_pd, best_set)) => {
+                improves(self.op, pd, *best_pd, || self.tree_pd.prefers_set(&self.chosen, best_set))
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::cmp::Ordering;
+
+use phylo::prelude::*;
+
+use super::error::PDError;
+use super::phylogenetic_diversity::RootedPhylogeneticDiversity;
+use super::{complement_dp, improves, TreePD};
+
+/// Default number of search nodes the branch-and-bound explores before it stops and
+/// reports the best viable set found so far
+pub const VIABLE_MAX_SEARCH_NODES: usize = 100_000;
+
+/// Dependencies between taxa, such as the prey of every taxon in a food web. A taxa
+/// set is viable when every taxon in it has no dependencies or has at least one of
+/// its dependencies in the set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyNetwork {
+    /// NodeIDs of the leaves every leaf depends on, indexed by NodeID
+    dependencies: Vec<Vec<usize>>,
+}
+
+/// A viable taxa set maximizing PD
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViableSelection<W> {
+    /// PD of the taxa set
+    pub pd: W,
+    /// NodeIDs of the taxa in the set
+    pub taxa: Vec<usize>,
+    /// Whether the set is proven optimal. This is false when the search was cut off,
+    /// in which case the set is the best one found by then.
+    pub exact: bool,
+}
+
+impl DependencyNetwork {
+    /// Builds the network from an edge list of (taxon, dependency) pairs of taxon labels
+    pub fn from_edges<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        tree_pd: &TreePD<'_,T,W,Z>,
+        edges: &[(T, T)],
+    ) -> Result<Self, PDError> {
+        let mut dependencies = vec![vec![]; tree_pd.edge_lengths.len()];
+        for (taxon, dependency) in edges {
+            dependencies[tree_pd.get_taxon_id(taxon)?].push(tree_pd.get_taxon_id(dependency)?);
+        }
+        Ok(DependencyNetwork { dependencies })
+    }
+
+    /// Returns true if every taxon in the set has no dependencies or has one of its
+    /// dependencies in the set
+    pub fn is_viable(&self, taxa: &[usize]) -> bool {
+        taxa.iter().all(|leaf_id| {
+            self.dependencies[*leaf_id].is_empty()
+                || self.dependencies[*leaf_id].iter().any(|x| taxa.contains(x))
+        })
+    }
+
+    /// Returns a viable set of a number of taxa maximizing PD, or None if there is no
+    /// viable set of that size
+    pub fn get_viable_maxPD<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        num_taxa: usize,
+    ) -> Option<ViableSelection<W>> {
+        self.get_viable_maxPD_with_limit(tree_pd, num_taxa, VIABLE_MAX_SEARCH_NODES)
+    }
+
+    /// Returns a viable set of a number of taxa maximizing PD by branch-and-bound,
+    /// deciding taxa in the order of the greedy maxPD ranking. The search starts from
+    /// the greedy heuristic set and bounds every partial set by the largest PD any
+    /// completion could reach while ignoring dependencies, computed with the complement
+    /// DP over the undecided taxa. If more than `max_search_nodes` search nodes are
+    /// needed, the best set found so far is returned and marked as not exact.
+    pub fn get_viable_maxPD_with_limit<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        num_taxa: usize,
+        max_search_nodes: usize,
+    ) -> Option<ViableSelection<W>> {
+        let order = tree_pd
+            .tree
+            .compute_maxPD_ranking(&tree_pd.edge_lengths)
+            .into_iter()
+            .map(|(leaf_id, _)| leaf_id)
+            .collect();
+        let mut search = Search {
+            tree_pd,
+            network: self,
+            order,
+            num_taxa,
+            chosen: vec![],
+            excluded: vec![false; tree_pd.edge_lengths.len()],
+            best: self.get_greedy_viable_maxPD(tree_pd, num_taxa),
+            nodes_left: max_search_nodes,
+        };
+        let exact = search.branch(0);
+        search.best.map(|(pd, taxa)| ViableSelection { pd, taxa, exact })
+    }
+
+    /// Returns a viable set of a number of taxa found greedily, or None if the
+    /// heuristic gets stuck. Taxa are added one at a time, each time choosing the taxon
+    /// with the largest gain in PD among those with no dependencies or with a
+    /// dependency already chosen. For acyclic networks this always finds a viable set
+    /// when one exists, but taxa that only support each other through a cycle are never
+    /// chosen.
+    pub fn get_greedy_viable_maxPD<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        num_taxa: usize,
+    ) -> Option<(W, Vec<usize>)> {
+        let tree = tree_pd.tree;
+        let mut covered = vec![false; tree_pd.edge_lengths.len()];
+        let mut chosen: Vec<usize> = vec![];
+        let mut pd = W::zero();
+        let gain = |covered: &[bool], leaf_id: usize| {
+            std::iter::successors(Some(leaf_id), |x| tree.get_node_parent_id(*x))
+                .take_while(|x| !covered[*x])
+                .map(|x| tree_pd.edge_lengths[x])
+                .sum::<W>()
+        };
+        while chosen.len() < num_taxa {
+            let mut best: Option<(W, usize)> = None;
+            for leaf_id in tree.get_leaf_ids() {
+                let is_addable = !covered[leaf_id]
+                    && (self.dependencies[leaf_id].is_empty()
+                        || self.dependencies[leaf_id].iter().any(|x| chosen.contains(x)));
+                if !is_addable {
+                    continue;
+                }
+                let leaf_gain = gain(&covered, leaf_id);
+                let replace = match best {
+                    None => true,
+                    Some((best_gain, best_id)) => {
+                        improves(Ordering::Greater, leaf_gain, best_gain, || tree_pd.prefers_set(&[leaf_id], &[best_id]))
+                    }
+                };
+                if replace {
+                    best = Some((leaf_gain, leaf_id));
+                }
+            }
+            let (leaf_gain, leaf_id) = best?;
+            pd = pd + leaf_gain;
+            chosen.push(leaf_id);
+            std::iter::successors(Some(leaf_id), |x| tree.get_node_parent_id(*x))
+                .take_while(|x| !covered[*x])
+                .collect::<Vec<_>>()
+                .into_iter()
+                .for_each(|x| covered[x] = true);
+        }
+        Some((pd, chosen))
+    }
+}
+
+/// State of the branch-and-bound search for a viable set maximizing PD
+struct Search<'a, 'b, T:NodeTaxa, W:EdgeWeight, Z:NodeWeight> {
+    tree_pd: &'a TreePD<'b,T,W,Z>,
+    network: &'a DependencyNetwork,
+    /// Order in which taxa are decided
+    order: Vec<usize>,
+    num_taxa: usize,
+    chosen: Vec<usize>,
+    excluded: Vec<bool>,
+    best: Option<(W, Vec<usize>)>,
+    nodes_left: usize,
+}
+
+impl<T:NodeTaxa, W:EdgeWeight, Z:NodeWeight> Search<'_, '_, T, W, Z> {
+    /// Explores all sets extending the chosen taxa with taxa from `order[depth..]`.
+    /// Returns false if the search was cut off.
+    fn branch(&mut self, depth: usize) -> bool {
+        if self.nodes_left == 0 {
+            return false;
+        }
+        self.nodes_left -= 1;
+        let dependencies = &self.network.dependencies;
+        // a chosen taxon whose dependencies were all left out can never be viable
+        if self.chosen.iter().any(|leaf_id| {
+            !dependencies[*leaf_id].is_empty() && dependencies[*leaf_id].iter().all(|x| self.excluded[*x])
+        }) {
+            return true;
+        }
+        let pd = self.tree_pd.taxa_set_pd(&self.chosen);
+        if self.chosen.len() == self.num_taxa {
+            if self.network.is_viable(&self.chosen) && self.improves(pd) {
+                self.best = Some((pd, self.chosen.clone()));
+            }
+            return true;
+        }
+        let tree = self.tree_pd.tree;
+        let gains = complement_dp(
+            tree,
+            Ordering::Greater,
+            &self.tree_pd.edge_lengths,
+            &self.chosen,
+            |leaf_id| !self.excluded[leaf_id],
+            self.tree_pd.options.tie_break,
+        );
+        match gains.get(self.num_taxa - self.chosen.len()) {
+            Some((gain, _)) if !self.best.as_ref().is_some_and(|(best_pd, _)| pd + *gain < *best_pd) => {},
+            _ => return true,
+        }
+        let leaf_id = self.order[depth];
+        self.chosen.push(leaf_id);
+        let complete = self.branch(depth + 1);
+        self.chosen.pop();
+        if !complete {
+            return false;
+        }
+        self.excluded[leaf_id] = true;
+        let complete = self.branch(depth + 1);
+        self.excluded[leaf_id] = false;
+        complete
+    }
+
+    /// Returns true if the chosen set with PD `pd` is better than the best set so far
+    fn improves(&self, pd: W) -> bool {
+        match &self.best {
+            None => true,
+            Some((best_pd, best_set)) => {
+                improves(Ordering::Greater, pd, *best_pd, || self.tree_pd.prefers_set(&self.chosen, best_set))
+            }
+        }
+    }
+}
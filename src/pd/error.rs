@@ -0,0 +1,69 @@
+use std::fmt::{Display, Formatter};
+
+/// Errors raised while preparing a tree for PD computations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PDError {
+    /// Edges without a branch length were found under `MissingLengthPolicy::Error`
+    MissingBranchLengths {
+        /// Number of edges without a branch length
+        num_missing: usize,
+    },
+    /// No edge in the tree has a branch length to derive a fill value from
+    NoBranchLengths,
+    /// Taxon label not found in the tree
+    UnknownTaxon(String),
+    /// Two or more leaves share a taxon label
+    DuplicateTaxon(String),
+    /// Leaves without a taxon label were found
+    UnlabelledLeaves {
+        /// Number of leaves without a taxon label
+        num_unlabelled: usize,
+    },
+    /// Node weights made some edge lengths negative, which the computation requested
+    /// does not support
+    NegativeEdgeLengths {
+        /// Number of edges with a negative weighted length
+        num_negative: usize,
+    },
+    /// A probability outside of [0, 1] was given for a taxon
+    InvalidProbability {
+        /// Taxon the probability was given for
+        taxon: String,
+        /// Value of the probability
+        probability: String,
+    },
+    /// Unrecognized name for a configurable option
+    UnknownOption {
+        /// Option being parsed
+        option: &'static str,
+        /// Value that could not be parsed
+        value: String,
+    },
+}
+
+impl Display for PDError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PDError::MissingBranchLengths { num_missing } => {
+                write!(f, "{} edge(s) have no branch length", num_missing)
+            }
+            PDError::NoBranchLengths => write!(f, "no edge in the tree has a branch length"),
+            PDError::UnknownTaxon(taxon) => write!(f, "taxon not found in tree: {}", taxon),
+            PDError::DuplicateTaxon(taxon) => write!(f, "taxon label shared by several leaves: {}", taxon),
+            PDError::UnlabelledLeaves { num_unlabelled } => {
+                write!(f, "{} leaf/leaves have no taxon label", num_unlabelled)
+            }
+            PDError::NegativeEdgeLengths { num_negative } => {
+                write!(f, "{} edge(s) have a negative length after applying node weights", num_negative)
+            }
+            PDError::InvalidProbability { taxon, probability } => {
+                write!(f, "probability for {} is not between 0 and 1: {}", taxon, probability)
+            }
+            PDError::UnknownOption { option, value } => {
+                write!(f, "unknown {}: {}", option, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PDError {}
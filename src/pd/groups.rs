@@ -0,0 +1,274 @@
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+use phylo::prelude::*;
+
+use super::error::PDError;
+use super::phylogenetic_diversity::{mrca_id, RootedPhylogeneticDiversity};
+use super::{clade_constrained_dp, complement_dp, improves, TreePD};
+
+/// Bounds on the number of taxa selected from a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupQuota {
+    /// Fewest taxa to select from the group
+    pub min: usize,
+    /// Most taxa to select from the group
+    pub max: usize,
+}
+
+impl Default for GroupQuota {
+    fn default() -> Self {
+        GroupQuota { min: 0, max: usize::MAX }
+    }
+}
+
+impl GroupQuota {
+    fn allows(&self, count: usize) -> bool {
+        self.min <= count && count <= self.max
+    }
+}
+
+/// Groups of taxa, such as regions or higher taxa, with quotas on the number of taxa
+/// selected from each. A taxon may belong to several groups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Groups {
+    /// NodeIDs of the leaves in every group
+    members: Vec<Vec<usize>>,
+    quotas: Vec<GroupQuota>,
+    /// Indices of the groups of every leaf, indexed by NodeID
+    groups_of: Vec<Vec<usize>>,
+}
+
+/// A taxa set meeting the group quotas with optimal PD
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaSelection<W> {
+    /// PD of the taxa set
+    pub pd: W,
+    /// NodeIDs of the taxa in the set
+    pub taxa: Vec<usize>,
+    /// Whether the set is proven optimal. This is false when the time limit of the
+    /// search was reached, in which case the set is the best one found by then.
+    pub exact: bool,
+}
+
+impl Groups {
+    /// Builds the groups from the taxon labels in every group and its quota
+    pub fn new<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        tree_pd: &TreePD<'_,T,W,Z>,
+        groups: &[(Vec<T>, GroupQuota)],
+    ) -> Result<Self, PDError> {
+        let mut groups_of = vec![vec![]; tree_pd.edge_lengths.len()];
+        let mut members = vec![];
+        for (group, (taxa, _)) in groups.iter().enumerate() {
+            let mut group_members = taxa.iter().map(|taxon| tree_pd.get_taxon_id(taxon)).collect::<Result<Vec<_>, _>>()?;
+            group_members.sort();
+            group_members.dedup();
+            group_members.iter().for_each(|leaf_id| groups_of[*leaf_id].push(group));
+            members.push(group_members);
+        }
+        let quotas = groups.iter().map(|(_, quota)| *quota).collect();
+        Ok(Groups { members, quotas, groups_of })
+    }
+
+    /// Returns true if the number of taxa of the set in every group is within its quota
+    pub fn is_feasible(&self, taxa: &[usize]) -> bool {
+        self.members
+            .iter()
+            .zip(self.quotas.iter())
+            .all(|(members, quota)| quota.allows(taxa.iter().filter(|x| members.contains(x)).count()))
+    }
+
+    /// Returns the node whose clade is exactly every group, or None if some group is
+    /// not a clade of the tree. Empty groups map to the root.
+    fn clade_ids<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(&self, tree_pd: &TreePD<'_,T,W,Z>) -> Option<Vec<usize>> {
+        let tree = tree_pd.tree;
+        self.members
+            .iter()
+            .map(|members| {
+                if members.is_empty() {
+                    return Some(tree.get_root_id());
+                }
+                let clade_id = mrca_id(tree, members);
+                let mut clade = tree.get_cluster_ids(clade_id).collect::<Vec<_>>();
+                clade.sort();
+                (clade == *members).then_some(clade_id)
+            })
+            .collect()
+    }
+
+    /// Returns a set of a number of taxa meeting the group quotas with the largest (for
+    /// `Ordering::Greater`) or smallest (for `Ordering::Less`) PD, or None if no such
+    /// set was found.
+    ///
+    /// When every group is a clade of the tree, quotas bound the number of taxa
+    /// selected below a node, and the set is found exactly by the PD DP restricted to
+    /// the allowed counts. Otherwise the set is found by branch-and-bound, bounding
+    /// every partial set by the best PD any completion could reach while ignoring the
+    /// quotas. When `time_limit` is reached, the best set found so far is returned
+    /// and marked as not exact.
+    pub fn get_quota_PD<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        op: Ordering,
+        num_taxa: usize,
+        time_limit: Duration,
+    ) -> Option<QuotaSelection<W>> {
+        match self.clade_ids(tree_pd) {
+            Some(clade_ids) => self.clade_quota_PD(tree_pd, op, num_taxa, &clade_ids),
+            None => self.search_quota_PD(tree_pd, op, num_taxa, time_limit),
+        }
+    }
+
+    fn clade_quota_PD<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        op: Ordering,
+        num_taxa: usize,
+        clade_ids: &[usize],
+    ) -> Option<QuotaSelection<W>> {
+        let mut node_quotas = vec![vec![]; tree_pd.edge_lengths.len()];
+        for (group, clade_id) in clade_ids.iter().enumerate() {
+            // an empty group constrains nothing but its own quota
+            let quota = match self.members[group].is_empty() {
+                true if self.quotas[group].allows(0) => continue,
+                true => return None,
+                false => self.quotas[group],
+            };
+            node_quotas[*clade_id].push(quota);
+        }
+        let table = clade_constrained_dp(
+            tree_pd.tree,
+            op,
+            &tree_pd.edge_lengths,
+            |_| Some(W::zero()),
+            |node_id, count| node_quotas[node_id].iter().all(|quota| quota.allows(count)),
+            tree_pd.options.tie_break,
+        );
+        let (pd, taxa) = table.into_iter().nth(num_taxa)??;
+        Some(QuotaSelection { pd, taxa, exact: true })
+    }
+
+    fn search_quota_PD<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        op: Ordering,
+        num_taxa: usize,
+        time_limit: Duration,
+    ) -> Option<QuotaSelection<W>> {
+        // decide the taxa adding the most PD first when maximizing, and last when minimizing
+        let mut order = tree_pd
+            .tree
+            .compute_maxPD_ranking(&tree_pd.edge_lengths)
+            .into_iter()
+            .map(|(leaf_id, _)| leaf_id)
+            .collect::<Vec<_>>();
+        if op == Ordering::Less {
+            order.reverse();
+        }
+        let mut search = Search {
+            tree_pd,
+            groups: self,
+            op,
+            order,
+            num_taxa,
+            chosen: vec![],
+            excluded: vec![false; tree_pd.edge_lengths.len()],
+            counts: vec![0; self.members.len()],
+            available: self.members.iter().map(|x| x.len()).collect(),
+            best: None,
+            deadline: Instant::now() + time_limit,
+        };
+        let exact = search.branch(0);
+        search.best.map(|(pd, taxa)| QuotaSelection { pd, taxa, exact })
+    }
+}
+
+/// State of the branch-and-bound search for a set meeting the group quotas
+struct Search<'a, 'b, T:NodeTaxa, W:EdgeWeight, Z:NodeWeight> {
+    tree_pd: &'a TreePD<'b,T,W,Z>,
+    groups: &'a Groups,
+    op: Ordering,
+    /// Order in which taxa are decided
+    order: Vec<usize>,
+    num_taxa: usize,
+    chosen: Vec<usize>,
+    excluded: Vec<bool>,
+    /// Number of chosen taxa in every group
+    counts: Vec<usize>,
+    /// Number of taxa in every group that are chosen or undecided
+    available: Vec<usize>,
+    best: Option<(W, Vec<usize>)>,
+    deadline: Instant,
+}
+
+impl<T:NodeTaxa, W:EdgeWeight, Z:NodeWeight> Search<'_, '_, T, W, Z> {
+    /// Explores all sets extending the chosen taxa with taxa from `order[depth..]`.
+    /// Returns false if the time limit was reached.
+    fn branch(&mut self, depth: usize) -> bool {
+        if Instant::now() >= self.deadline {
+            return false;
+        }
+        let quotas = &self.groups.quotas;
+        if (0..quotas.len()).any(|group| self.counts[group] > quotas[group].max || self.available[group] < quotas[group].min) {
+            return true;
+        }
+        let pd = self.tree_pd.taxa_set_pd(&self.chosen);
+        if self.chosen.len() == self.num_taxa {
+            if (0..quotas.len()).all(|group| self.counts[group] >= quotas[group].min) && self.improves(pd) {
+                self.best = Some((pd, self.chosen.clone()));
+            }
+            return true;
+        }
+        if depth == self.order.len() {
+            return true;
+        }
+        // taxa in a full group can no longer be chosen
+        let is_blocked = |leaf_id: usize| {
+            self.groups.groups_of[leaf_id].iter().any(|group| self.counts[*group] >= quotas[*group].max)
+        };
+        let gains = complement_dp(
+            self.tree_pd.tree,
+            self.op,
+            &self.tree_pd.edge_lengths,
+            &self.chosen,
+            |leaf_id| !self.excluded[leaf_id] && !is_blocked(leaf_id),
+            self.tree_pd.options.tie_break,
+        );
+        // prune when the best set so far beats every completion
+        let bound = match gains.get(self.num_taxa - self.chosen.len()) {
+            Some((gain, _)) => pd + *gain,
+            None => return true,
+        };
+        if self.best.as_ref().is_some_and(|(best_pd, _)| best_pd.partial_cmp(&bound) == Some(self.op)) {
+            return true;
+        }
+        let leaf_id = self.order[depth];
+        let leaf_groups = &self.groups.groups_of[leaf_id];
+        if !is_blocked(leaf_id) {
+            self.chosen.push(leaf_id);
+            leaf_groups.iter().for_each(|group| self.counts[*group] += 1);
+            let complete = self.branch(depth + 1);
+            leaf_groups.iter().for_each(|group| self.counts[*group] -= 1);
+            self.chosen.pop();
+            if !complete {
+                return false;
+            }
+        }
+        self.excluded[leaf_id] = true;
+        leaf_groups.iter().for_each(|group| self.available[*group] -= 1);
+        let complete = self.branch(depth + 1);
+        leaf_groups.iter().for_each(|group| self.available[*group] += 1);
+        self.excluded[leaf_id] = false;
+        complete
+    }
+
+    /// Returns true if the chosen set with PD `pd` is better than the best set so far
+    fn improves(&self, pd: W) -> bool {
+        match &self.best {
+            None => true,
+            Some((best_pd, best_set)) => {
+                improves(self.op, pd, *best_pd, || self.tree_pd.prefers_set(&self.chosen, best_set))
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use phylo::prelude::*;
+
+use super::error::PDError;
+use super::TreePD;
+
+/// A conservation project for a taxon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Project<W> {
+    /// Integer cost of the project
+    pub cost: usize,
+    /// Probability that the taxon survives if the project is funded
+    pub survival: W,
+}
+
+/// Projects funded by a solution of the generalized Noah's Ark problem
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArkSolution<W> {
+    /// Expected PD of the taxa surviving
+    pub expected_pd: W,
+    /// Total cost of the funded projects
+    pub cost: usize,
+    /// Indices of the funded projects in the list of projects, at most one per taxon
+    pub projects: Vec<usize>,
+}
+
+/// Expected PD below a node (excluding the edge into it), probability that no taxon
+/// below it survives, and the projects funded below it
+type FrontPoint<W> = (W, W, Vec<usize>);
+
+/// Solves the generalized Noah's Ark problem: funds at most one project per taxon
+/// within a budget so as to maximize expected PD. An edge contributes its length
+/// times the probability that some taxon below it survives, and taxa without a funded
+/// project are lost (list a zero-cost project to give a taxon a baseline survival
+/// probability).
+///
+/// The DP runs over the tree and over the exact cost spent below every node. Since the
+/// objective is not additive, every entry keeps the Pareto front of the expected PD
+/// below the node against the probability that nothing below it survives, which makes
+/// the DP exact. It is pseudo-polynomial in the budget, times the sizes of the fronts.
+/// The fronts are only valid for non-negative edge lengths, so negative lengths are
+/// rejected.
+pub fn solve_noahs_ark<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree_pd: &TreePD<'_,T,W,Z>,
+    projects: &[(T, Project<W>)],
+    budget: usize,
+) -> Result<ArkSolution<W>, PDError> {
+    tree_pd.check_non_negative_lengths()?;
+    let tree = tree_pd.tree;
+    let mut leaf_projects = vec![vec![]; tree_pd.edge_lengths.len()];
+    for (row, (taxon, project)) in projects.iter().enumerate() {
+        if !(project.survival >= W::zero() && project.survival <= W::one()) {
+            return Err(PDError::InvalidProbability {
+                taxon: taxon.to_string(),
+                probability: project.survival.to_string(),
+            });
+        }
+        leaf_projects[tree_pd.get_taxon_id(taxon)?].push(row);
+    }
+
+    let mut tables: Vec<Vec<Vec<FrontPoint<W>>>> = vec![vec![]; tree_pd.edge_lengths.len()];
+    for node_id in tree.postord_ids(tree.get_root_id()) {
+        let mut table = vec![vec![]; budget + 1];
+        table[0].push((W::zero(), W::one(), vec![]));
+        if tree.is_leaf(node_id) {
+            for row in leaf_projects[node_id].iter() {
+                let project = &projects[*row].1;
+                if project.cost <= budget {
+                    table[project.cost].push((W::zero(), W::one() - project.survival, vec![*row]));
+                }
+            }
+            table.iter_mut().for_each(prune_front);
+            tables[node_id] = table;
+            continue;
+        }
+        for child_id in tree.get_node_children_ids(node_id).collect::<Vec<_>>() {
+            let child_table = std::mem::take(&mut tables[child_id]);
+            let w = tree_pd.edge_lengths[child_id];
+            let mut merged = vec![vec![]; budget + 1];
+            for (cost_l, front_l) in table.iter().enumerate() {
+                for (cost_r, front_r) in child_table.iter().enumerate().take(budget + 1 - cost_l) {
+                    for (val_l, lost_l, set_l) in front_l {
+                        for (val_r, lost_r, set_r) in front_r {
+                            merged[cost_l + cost_r].push((
+                                *val_l + *val_r + w * (W::one() - *lost_r),
+                                *lost_l * *lost_r,
+                                set_l.iter().chain(set_r.iter()).copied().collect(),
+                            ));
+                        }
+                    }
+                }
+            }
+            merged.iter_mut().for_each(prune_front);
+            table = merged;
+        }
+        tables[node_id] = table;
+    }
+
+    // the cheapest solution attaining the largest expected PD
+    let mut solution = ArkSolution {
+        expected_pd: W::zero(),
+        cost: 0,
+        projects: vec![],
+    };
+    for (cost, front) in tables.swap_remove(tree.get_root_id()).into_iter().enumerate() {
+        for (expected_pd, _, mut set) in front {
+            if expected_pd > solution.expected_pd {
+                set.sort();
+                solution = ArkSolution { expected_pd, cost, projects: set };
+            }
+        }
+    }
+    Ok(solution)
+}
+
+/// Keeps the points of a front not dominated by a point with at least as much expected
+/// PD and at most the same probability of losing every taxon
+fn prune_front<W:EdgeWeight>(front: &mut Vec<FrontPoint<W>>) {
+    front.sort_by(|(val_a, lost_a, _), (val_b, lost_b, _)| {
+        val_b.partial_cmp(val_a).unwrap().then(lost_a.partial_cmp(lost_b).unwrap())
+    });
+    let mut min_lost = W::infinity();
+    front.retain(|(_, lost, _)| {
+        let keep = *lost < min_lost;
+        min_lost = min_lost.min(*lost);
+        keep
+    });
+}
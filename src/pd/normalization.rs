@@ -0,0 +1,87 @@
+use std::str::FromStr;
+
+use phylo::prelude::*;
+
+use super::error::PDError;
+
+/// Quantities describing a taxa set that a normalization may divide its PD by
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxaSetSummary<W> {
+    /// PD of the taxa set
+    pub pd: W,
+    /// Number of taxa in the set
+    pub num_taxa: usize,
+    /// Number of positive-length edges spanned by the taxa set
+    pub num_edges: u32,
+    /// PD of all leaves in the clade of the most recent common ancestor of the set
+    pub mrca_clade_pd: W,
+    /// Total branch length of the tree
+    pub tree_length: W,
+    /// Largest distance from the most recent common ancestor of the set down to one of its taxa
+    pub crown_age: W,
+}
+
+/// Normalization of the PD of a taxa set, used to compare sets of different sizes
+/// when computing generalized PD
+pub trait Normalization<W: EdgeWeight> {
+    /// Returns the normalized PD of a taxa set
+    fn normalize(&self, summary: &TaxaSetSummary<W>) -> W;
+
+    /// Whether the normalized PD depends on the most recent common ancestor of the set,
+    /// through `mrca_clade_pd` or `crown_age`. Generalized PD then also compares the
+    /// optimal PD sets below every possible ancestor, which takes longer.
+    fn depends_on_mrca(&self) -> bool {
+        true
+    }
+}
+
+/// Built-in normalizations of PD
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NormalizationKind {
+    /// PD per positive-length edge spanned
+    #[default]
+    Edges,
+    /// PD per taxon
+    Taxa,
+    /// PD as a fraction of the total branch length of the tree
+    TreeLength,
+    /// PD as a fraction of the PD of the clade of the set's most recent common ancestor
+    MrcaClade,
+    /// PD per unit of crown age of the set
+    CrownAge,
+}
+
+impl<W: EdgeWeight> Normalization<W> for NormalizationKind {
+    fn normalize(&self, summary: &TaxaSetSummary<W>) -> W {
+        let denominator = match self {
+            NormalizationKind::Edges => W::from(summary.num_edges).unwrap(),
+            NormalizationKind::Taxa => W::from(summary.num_taxa).unwrap(),
+            NormalizationKind::TreeLength => summary.tree_length,
+            NormalizationKind::MrcaClade => summary.mrca_clade_pd,
+            NormalizationKind::CrownAge => summary.crown_age,
+        };
+        summary.pd / denominator
+    }
+
+    fn depends_on_mrca(&self) -> bool {
+        matches!(self, NormalizationKind::MrcaClade | NormalizationKind::CrownAge)
+    }
+}
+
+impl FromStr for NormalizationKind {
+    type Err = PDError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "edges" => Ok(NormalizationKind::Edges),
+            "taxa" => Ok(NormalizationKind::Taxa),
+            "tree_length" => Ok(NormalizationKind::TreeLength),
+            "mrca_clade" => Ok(NormalizationKind::MrcaClade),
+            "crown_age" => Ok(NormalizationKind::CrownAge),
+            _ => Err(PDError::UnknownOption {
+                option: "normalization",
+                value: s.to_string(),
+            }),
+        }
+    }
+}
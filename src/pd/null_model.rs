@@ -0,0 +1,289 @@
+use std::cmp::min;
+
+use itertools::Itertools;
+use phylo::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use super::normalization::{Normalization, NormalizationKind, TaxaSetSummary};
+use super::{edge_counts, TreePD};
+
+/// Default number of random taxa sets drawn when the null distribution of normalized PD
+/// is estimated by Monte Carlo
+pub const NULL_SAMPLES: usize = 10_000;
+
+/// Largest number of table merges for which the null distribution of normalized PD is
+/// computed exactly. Larger trees fall back to Monte Carlo.
+pub const NULL_EXACT_MAX_OPERATIONS: usize = 100_000_000;
+
+/// Mean and standard deviation of normalized PD over uniformly random taxa sets of a
+/// fixed size
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullDistribution<W> {
+    /// Expected normalized PD
+    pub mean: W,
+    /// Standard deviation of normalized PD
+    pub sd: W,
+    /// Whether the moments are exact. Otherwise they are Monte Carlo estimates, and the
+    /// standard error of the mean is `sd / sqrt(samples)`.
+    pub exact: bool,
+    /// Number of random taxa sets with a finite normalized PD the estimates are based on,
+    /// or zero if exact
+    pub samples: usize,
+}
+
+/// Sums over the taxa sets of a clade with a given size spanning a given number of edges
+#[derive(Debug, Clone, Copy, Default)]
+struct Moments {
+    count: f64,
+    pd: f64,
+    pd_squared: f64,
+}
+
+impl Moments {
+    /// Moments after adding an edge of length `w` to every set
+    fn with_edge(self, w: f64) -> Self {
+        Moments {
+            count: self.count,
+            pd: self.pd + w * self.count,
+            pd_squared: self.pd_squared + 2.0 * w * self.pd + w * w * self.count,
+        }
+    }
+
+    /// Moments of the unions of a set from each of two disjoint clades
+    fn join(&self, other: &Self) -> Self {
+        Moments {
+            count: self.count * other.count,
+            pd: self.pd * other.count + other.pd * self.count,
+            pd_squared: self.pd_squared * other.count + other.pd_squared * self.count + 2.0 * self.pd * other.pd,
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.count += other.count;
+        self.pd += other.pd;
+        self.pd_squared += other.pd_squared;
+    }
+}
+
+/// Returns the mean and standard deviation of normalized PD over uniformly random sets
+/// of a number of taxa, as a null model to judge whether an observed normalized PD is
+/// unusual. Sets whose normalized PD is not finite, such as sets spanning no edge of
+/// positive length, are left out. Returns None if no set of that size has a finite
+/// normalized PD.
+///
+/// PD divided by the number of edges (or taxa, or the tree length) is a ratio whose
+/// denominator only depends on the number of edges spanned, so its moments are
+/// computed exactly by a DP over the number of taxa and edges below every node. Ratios
+/// involving the most recent common ancestor of the set are intractable this way, and
+/// are estimated from `samples` random sets drawn with `seed`, as are the others when
+/// the DP needs more than `NULL_EXACT_MAX_OPERATIONS` merges.
+pub fn get_null_normPD<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree_pd: &TreePD<'_,T,W,Z>,
+    norm: NormalizationKind,
+    num_taxa: usize,
+    samples: usize,
+    seed: u64,
+) -> Option<NullDistribution<W>> {
+    let num_leaves = tree_pd.tree.get_leaf_ids().count();
+    if num_taxa == 0 || num_taxa > num_leaves {
+        return None;
+    }
+    match exact_root_table(tree_pd, norm, num_taxa) {
+        Some(root_table) => summarize(exact_moments(tree_pd, &root_table, norm, num_taxa)?, true),
+        None => summarize(sampled_moments(tree_pd, norm, num_taxa, samples, seed).swap_remove(num_taxa), false),
+    }
+}
+
+/// Returns the null distribution of normalized PD for every number of taxa, indexed by
+/// that number, as `get_null_normPD` would. The exact DP is run once for all sizes,
+/// and all of them are estimated by Monte Carlo if it is too large.
+pub fn get_null_normPD_profile<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree_pd: &TreePD<'_,T,W,Z>,
+    norm: NormalizationKind,
+    samples: usize,
+    seed: u64,
+) -> Vec<Option<NullDistribution<W>>> {
+    let num_leaves = tree_pd.tree.get_leaf_ids().count();
+    match exact_root_table(tree_pd, norm, num_leaves) {
+        Some(root_table) => std::iter::once(None)
+            .chain((1..num_leaves + 1).map(|num_taxa| summarize(exact_moments(tree_pd, &root_table, norm, num_taxa)?, true)))
+            .collect(),
+        None => sampled_moments(tree_pd, norm, num_leaves, samples, seed)
+            .into_iter()
+            .map(|moments| summarize(moments, false))
+            .collect(),
+    }
+}
+
+/// Denominator of normalized PD for sets of a number of taxa spanning a number of
+/// edges, for normalizations that depend on nothing else
+fn denominator<W:EdgeWeight>(norm: NormalizationKind, num_taxa: usize, num_edges: usize, tree_length: W) -> f64 {
+    match norm {
+        NormalizationKind::Edges => num_edges as f64,
+        NormalizationKind::Taxa => num_taxa as f64,
+        _ => tree_length.to_f64().unwrap(),
+    }
+}
+
+/// Sums of PD and its square over the sets of up to `max_taxa` taxa, indexed by number
+/// of taxa and of edges spanned, or None if the DP is too large or the normalization
+/// involves the most recent common ancestor. Edges are only counted when normalizing
+/// by them.
+fn exact_root_table<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree_pd: &TreePD<'_,T,W,Z>,
+    norm: NormalizationKind,
+    max_taxa: usize,
+) -> Option<Vec<Vec<Moments>>> {
+    if Normalization::<W>::depends_on_mrca(&norm) {
+        return None;
+    }
+    let tree = tree_pd.tree;
+    let counts = edge_counts(tree, &tree_pd.edge_lengths);
+    let mut operations = 0;
+    // moments of the sets below every node, indexed by number of taxa and of edges
+    let mut tables: Vec<Vec<Vec<Moments>>> = vec![vec![]; tree_pd.edge_lengths.len()];
+    let unit = Moments { count: 1.0, ..Default::default() };
+    for node_id in tree.postord_ids(tree.get_root_id()) {
+        let mut table = vec![vec![unit]];
+        if tree.is_leaf(node_id) {
+            table.push(vec![unit]);
+        }
+        for child_id in tree.get_node_children_ids(node_id).collect_vec() {
+            let child_table = std::mem::take(&mut tables[child_id]);
+            let w = tree_pd.edge_lengths[child_id].to_f64().unwrap();
+            let shift = match norm {
+                NormalizationKind::Edges => counts[child_id] as usize,
+                _ => 0,
+            };
+            let num_edges = table.iter().map(Vec::len).max().unwrap() + child_table.iter().map(Vec::len).max().unwrap() + shift - 1;
+            let mut merged = vec![vec![Moments::default(); num_edges]; min(table.len() + child_table.len() - 1, max_taxa + 1)];
+            operations += table.iter().map(Vec::len).sum::<usize>() * child_table.iter().map(Vec::len).sum::<usize>();
+            if operations > NULL_EXACT_MAX_OPERATIONS {
+                return None;
+            }
+            for (l, row_l) in table.iter().enumerate() {
+                for (r, row_r) in child_table.iter().enumerate().take(max_taxa + 1 - l) {
+                    // the edge into the child is spanned once the child clade has taxa
+                    let (w, shift) = if r > 0 { (w, shift) } else { (0.0, 0) };
+                    for (e_l, moments_l) in row_l.iter().enumerate() {
+                        for (e_r, moments_r) in row_r.iter().enumerate() {
+                            merged[l + r][e_l + e_r + shift].add(&moments_l.join(&moments_r.with_edge(w)));
+                        }
+                    }
+                }
+            }
+            table = merged;
+        }
+        tables[node_id] = table;
+    }
+    Some(tables.swap_remove(tree.get_root_id()))
+}
+
+/// Sums of normalized PD and its square over all sets of a number of taxa, or None if
+/// the number of sets overflows
+fn exact_moments<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree_pd: &TreePD<'_,T,W,Z>,
+    root_table: &[Vec<Moments>],
+    norm: NormalizationKind,
+    num_taxa: usize,
+) -> Option<Moments> {
+    let tree_length = tree_pd.edge_lengths.iter().copied().sum::<W>();
+    let mut moments = Moments::default();
+    for (num_edges, sums) in root_table.get(num_taxa)?.iter().enumerate() {
+        let d = denominator(norm, num_taxa, num_edges, tree_length);
+        if sums.count == 0.0 || d == 0.0 {
+            continue;
+        }
+        moments.add(&Moments { count: sums.count, pd: sums.pd / d, pd_squared: sums.pd_squared / (d * d) });
+    }
+    moments.count.is_finite().then_some(moments)
+}
+
+/// Sums of normalized PD and its square over random sets of up to `max_taxa` taxa,
+/// indexed by number of taxa, leaving out sets whose normalized PD is not finite. The
+/// sets of every size are the prefixes of `samples` random orderings of the leaves.
+fn sampled_moments<T:NodeTaxa,W:EdgeWeight,Z:NodeWeight>(
+    tree_pd: &TreePD<'_,T,W,Z>,
+    norm: NormalizationKind,
+    max_taxa: usize,
+    samples: usize,
+    seed: u64,
+) -> Vec<Moments> {
+    let tree = tree_pd.tree;
+    let counts = edge_counts(tree, &tree_pd.edge_lengths);
+    let mut leaf_ids = tree.get_leaf_ids().collect_vec();
+    let tree_length = tree_pd.edge_lengths.iter().copied().sum::<W>();
+    // distance of every node from the root, in length and in edges, and length of the
+    // edges below it
+    let mut depths = vec![W::zero(); tree_pd.edge_lengths.len()];
+    let mut levels = vec![0; tree_pd.edge_lengths.len()];
+    for node_id in tree.preord_ids(tree.get_root_id()) {
+        if let Some(parent_id) = tree.get_node_parent_id(node_id) {
+            depths[node_id] = depths[parent_id] + tree_pd.edge_lengths[node_id];
+            levels[node_id] = levels[parent_id] + 1;
+        }
+    }
+    let mut clade_lengths = vec![W::zero(); tree_pd.edge_lengths.len()];
+    for node_id in tree.postord_ids(tree.get_root_id()) {
+        if let Some(parent_id) = tree.get_node_parent_id(node_id) {
+            clade_lengths[parent_id] = clade_lengths[parent_id] + clade_lengths[node_id] + tree_pd.edge_lengths[node_id];
+        }
+    }
+    // last sample whose taxa reach every node
+    let mut visited = vec![usize::MAX; tree_pd.edge_lengths.len()];
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut moments = vec![Moments::default(); max_taxa + 1];
+    for sample in 0..samples {
+        leaf_ids.shuffle(&mut rng);
+        let mut num_edges = 0;
+        let mut pd = W::zero();
+        let mut mrca_id = leaf_ids[0];
+        let mut max_depth = W::zero();
+        for (num_taxa, leaf_id) in leaf_ids.iter().take(max_taxa).enumerate() {
+            let mut node_id = *leaf_id;
+            while let Some(parent_id) = tree.get_node_parent_id(node_id) {
+                if visited[node_id] == sample {
+                    break;
+                }
+                visited[node_id] = sample;
+                num_edges += counts[node_id];
+                pd = pd + tree_pd.edge_lengths[node_id];
+                node_id = parent_id;
+            }
+            // the path from a new taxon joins those of the others below their most
+            // recent common ancestor, or at a new one above it
+            if num_taxa > 0 && levels[node_id] < levels[mrca_id] {
+                mrca_id = node_id;
+            }
+            max_depth = max_depth.max(depths[*leaf_id]);
+            let value = norm.normalize(&TaxaSetSummary {
+                pd,
+                num_taxa: num_taxa + 1,
+                num_edges,
+                mrca_clade_pd: depths[mrca_id] + clade_lengths[mrca_id],
+                tree_length,
+                crown_age: max_depth - depths[mrca_id],
+            });
+            if value.is_finite() {
+                let value = value.to_f64().unwrap();
+                moments[num_taxa + 1].add(&Moments { count: 1.0, pd: value, pd_squared: value * value });
+            }
+        }
+    }
+    moments
+}
+
+/// Mean and standard deviation from the sums of normalized PD and its square
+fn summarize<W:EdgeWeight>(moments: Moments, exact: bool) -> Option<NullDistribution<W>> {
+    if moments.count == 0.0 {
+        return None;
+    }
+    let mean = moments.pd / moments.count;
+    let variance = (moments.pd_squared / moments.count - mean * mean).max(0.0);
+    Some(NullDistribution {
+        mean: W::from(mean).unwrap(),
+        sd: W::from(variance.sqrt()).unwrap(),
+        exact,
+        samples: if exact { 0 } else { moments.count as usize },
+    })
+}
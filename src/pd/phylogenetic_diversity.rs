@@ -1,7 +1,108 @@
 use itertools::Itertools;
 use phylo::prelude::*;
 
-use std::{cmp::{max, min, Ordering}, collections::VecDeque};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{cmp::{max, min, Ordering}, collections::{HashMap, HashSet}, str::FromStr};
+
+use super::error::PDError;
+
+/// How edges without a branch length are treated by the PD computations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingLengthPolicy {
+    /// Fail if any edge is missing a branch length
+    Error,
+    /// Treat missing lengths as zero; such edges are not counted when normalizing
+    #[default]
+    Zero,
+    /// Treat every missing length as 1 (unit-length cladogram)
+    Unit,
+    /// Fill missing lengths with the mean of the lengths that are present
+    Mean,
+}
+
+impl FromStr for MissingLengthPolicy {
+    type Err = PDError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(MissingLengthPolicy::Error),
+            "zero" => Ok(MissingLengthPolicy::Zero),
+            "unit" => Ok(MissingLengthPolicy::Unit),
+            "mean" => Ok(MissingLengthPolicy::Mean),
+            _ => Err(PDError::UnknownOption {
+                option: "missing length policy",
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// How node weights (the `Z` annotation of nodes) enter the PD computations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeWeightPolicy {
+    /// Node weights are not used
+    #[default]
+    Ignore,
+    /// The weight of every node spanned by a taxa set is added to its PD
+    Bonus,
+    /// The weight of every node spanned by a taxa set is subtracted from its PD
+    Cost,
+}
+
+impl FromStr for NodeWeightPolicy {
+    type Err = PDError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(NodeWeightPolicy::Ignore),
+            "bonus" => Ok(NodeWeightPolicy::Bonus),
+            "cost" => Ok(NodeWeightPolicy::Cost),
+            _ => Err(PDError::UnknownOption {
+                option: "node weight policy",
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// Rule used to choose between optimal sets of equal value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    /// Prefer the set whose sorted taxon labels are lexicographically smallest
+    #[default]
+    Lexicographic,
+    /// Prefer the set spanning the fewest edges, then the lexicographically smallest
+    FewestEdges,
+    /// Prefer the set spanning the most edges, then the lexicographically smallest
+    MostEdges,
+}
+
+impl FromStr for TieBreak {
+    type Err = PDError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lexicographic" => Ok(TieBreak::Lexicographic),
+            "fewest_edges" => Ok(TieBreak::FewestEdges),
+            "most_edges" => Ok(TieBreak::MostEdges),
+            _ => Err(PDError::UnknownOption {
+                option: "tie-breaking rule",
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// Options controlling how PD tables are computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PDOptions {
+    /// Treatment of edges without a branch length
+    pub missing_lengths: MissingLengthPolicy,
+    /// Rule used to choose between optimal sets of equal value
+    pub tie_break: TieBreak,
+    /// Use of node weights as bonuses or costs
+    pub node_weights: NodeWeightPolicy,
+}
 
 pub trait TreePDMap
 where 
@@ -12,10 +113,19 @@ where
     fn get_tree(&self)->&Self::Tree;
     fn reset(&mut self);
 
+    /// Returns the NodeID of the most recent common ancestor of a set of leaves, by
+    /// taxon label, failing if a label is unknown or leaf labels are not unique
+    fn get_clade_id(
+        &self,
+        taxa: &[TreeNodeMeta<Self::Tree>],
+    ) -> Result<TreeNodeID<Self::Tree>, PDError>;
+
     fn get_minPD(
         &self,
         num_taxa: usize,
-    ) -> TreeNodeWeight<Self::Tree>;
+    ) -> TreeNodeWeight<Self::Tree> {
+        self.get_minPD_node(self.get_tree().get_root_id(), num_taxa)
+    }
 
     fn get_minPD_node(
         &self,
@@ -26,31 +136,74 @@ where
     fn get_norm_minPD(
         &self,
         num_taxa: usize,
-    ) -> TreeNodeWeight<Self::Tree>;
+    ) -> TreeNodeWeight<Self::Tree> {
+        self.get_norm_minPD_node(self.get_tree().get_root_id(), num_taxa)
+    }
 
+    fn get_norm_minPD_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+        num_taxa: usize,
+    ) -> TreeNodeWeight<Self::Tree>;
 
     fn get_minPD_taxa_set(
         &self,
         num_taxa: usize,
+    ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
+        self.get_minPD_taxa_set_node(self.get_tree().get_root_id(), num_taxa)
+    }
+
+    fn get_minPD_taxa_set_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+        num_taxa: usize,
     ) -> impl Iterator<Item = TreeNodeID<Self::Tree>>;
 
     fn get_norm_minPD_taxa_set(
         &self,
         num_taxa: usize,
+    ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
+        self.get_norm_minPD_taxa_set_node(self.get_tree().get_root_id(), num_taxa)
+    }
+
+    fn get_norm_minPD_taxa_set_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+        num_taxa: usize,
     ) -> impl Iterator<Item = TreeNodeID<Self::Tree>>;
 
     fn get_min_genPD(
         &self,
-    ) -> TreeNodeWeight<Self::Tree>;
+    ) -> Option<TreeNodeWeight<Self::Tree>> {
+        self.get_min_genPD_node(self.get_tree().get_root_id())
+    }
+
+    /// Returns the minimum generalized PD of a clade, or `None` if the clade has no
+    /// subset of at least three taxa with non-zero PD
+    fn get_min_genPD_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+    ) -> Option<TreeNodeWeight<Self::Tree>>;
 
     fn get_min_genPD_set(
         &self,
-    ) -> impl Iterator<Item = TreeNodeID<Self::Tree>>;
+    ) -> Option<impl Iterator<Item = TreeNodeID<Self::Tree>>> {
+        self.get_min_genPD_set_node(self.get_tree().get_root_id())
+    }
+
+    /// Returns the minimum generalized PD set of a clade, or `None` if the clade has no
+    /// subset of at least three taxa with non-zero PD
+    fn get_min_genPD_set_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+    ) -> Option<impl Iterator<Item = TreeNodeID<Self::Tree>>>;
 
     fn get_maxPD(
         &self,
         num_taxa: usize,
-    ) -> TreeNodeWeight<Self::Tree>;
+    ) -> TreeNodeWeight<Self::Tree> {
+        self.get_maxPD_node(self.get_tree().get_root_id(), num_taxa)
+    }
 
     fn get_maxPD_node(
         &self,
@@ -61,40 +214,178 @@ where
     fn get_norm_maxPD(
         &self,
         num_taxa: usize,
+    ) -> TreeNodeWeight<Self::Tree> {
+        self.get_norm_maxPD_node(self.get_tree().get_root_id(), num_taxa)
+    }
+
+    fn get_norm_maxPD_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+        num_taxa: usize,
     ) -> TreeNodeWeight<Self::Tree>;
 
     fn get_maxPD_taxa_set(
         &self,
         num_taxa: usize,
+    ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
+        self.get_maxPD_taxa_set_node(self.get_tree().get_root_id(), num_taxa)
+    }
+
+    fn get_maxPD_taxa_set_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+        num_taxa: usize,
     ) -> impl Iterator<Item = TreeNodeID<Self::Tree>>;
 
     fn get_norm_maxPD_taxa_set(
         &self,
         num_taxa: usize,
+    ) -> impl Iterator<Item = TreeNodeID<Self::Tree>> {
+        self.get_norm_maxPD_taxa_set_node(self.get_tree().get_root_id(), num_taxa)
+    }
+
+    fn get_norm_maxPD_taxa_set_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+        num_taxa: usize,
     ) -> impl Iterator<Item = TreeNodeID<Self::Tree>>;
 
     fn get_max_genPD(
         &self,
-    ) -> TreeNodeWeight<Self::Tree>;
+    ) -> Option<TreeNodeWeight<Self::Tree>> {
+        self.get_max_genPD_node(self.get_tree().get_root_id())
+    }
+
+    /// Returns the maximum generalized PD of a clade, or `None` if the clade has no
+    /// subset of at least three taxa with non-zero PD
+    fn get_max_genPD_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+    ) -> Option<TreeNodeWeight<Self::Tree>>;
 
     fn get_max_genPD_set(
         &self,
-    ) -> impl Iterator<Item = TreeNodeID<Self::Tree>>;
+    ) -> Option<impl Iterator<Item = TreeNodeID<Self::Tree>>> {
+        self.get_max_genPD_set_node(self.get_tree().get_root_id())
+    }
+
+    /// Returns the maximum generalized PD set of a clade, or `None` if the clade has no
+    /// subset of at least three taxa with non-zero PD
+    fn get_max_genPD_set_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+    ) -> Option<impl Iterator<Item = TreeNodeID<Self::Tree>>>;
+
+    /// Returns the smallest number of taxa whose maxPD reaches a target, along with a
+    /// maxPD set of that size, or `None` if no number of taxa reaches it. A fraction of
+    /// the total tree length can be targeted with `fraction * get_maxPD(n)`. Every
+    /// number of taxa is checked, since maxPD is not monotone in it when node weights
+    /// make edge lengths negative.
+    fn get_min_taxa_reaching_PD(
+        &self,
+        target: TreeNodeWeight<Self::Tree>,
+    ) -> Option<(usize, Vec<TreeNodeID<Self::Tree>>)> {
+        let num_leaves = self.get_tree().get_leaves().len();
+        let num_taxa = (0..num_leaves + 1).find(|k| self.get_maxPD(*k) >= target)?;
+        Some((num_taxa, self.get_maxPD_taxa_set(num_taxa).collect()))
+    }
+
+    /// Returns the largest number of taxa whose minPD stays within a ceiling, along with
+    /// a minPD set of that size, or `None` if no number of taxa does. Every number of
+    /// taxa is checked, since minPD is not monotone in it when node weights make edge
+    /// lengths negative.
+    fn get_max_taxa_within_PD(
+        &self,
+        ceiling: TreeNodeWeight<Self::Tree>,
+    ) -> Option<(usize, Vec<TreeNodeID<Self::Tree>>)> {
+        let num_leaves = self.get_tree().get_leaves().len();
+        let num_taxa = (0..num_leaves + 1).rev().find(|k| self.get_minPD(*k) <= ceiling)?;
+        Some((num_taxa, self.get_minPD_taxa_set(num_taxa).collect()))
+    }
 
     fn get_avgPD(
         &self,
         num_taxa: usize,
+    ) -> TreeNodeWeight<Self::Tree> {
+        self.get_avgPD_node(self.get_tree().get_root_id(), num_taxa)
+    }
+
+    fn get_avgPD_node(
+        &self,
+        node_id: TreeNodeID<Self::Tree>,
+        num_taxa: usize,
     ) -> TreeNodeWeight<Self::Tree>;
 
 }
 
+/// Returns the NodeID of the most recent common ancestor of a set of nodes, or the root if the set is empty
+pub(crate) fn mrca_id<T: RootedTree>(tree: &T, node_ids: &[TreeNodeID<T>])->TreeNodeID<T>{
+    let mut mrca_path = match node_ids.first() {
+        Some(node_id) => path_to_root(tree, *node_id),
+        None => return tree.get_root_id(),
+    };
+    for node_id in node_ids.iter().skip(1) {
+        let ancestors = path_to_root(tree, *node_id).into_iter().collect::<HashSet<_>>();
+        let pos = mrca_path.iter().position(|x| ancestors.contains(x)).unwrap();
+        mrca_path.drain(..pos);
+    }
+    mrca_path[0]
+}
+
+/// Returns the NodeIDs on the path from a node up to the root
+fn path_to_root<T: RootedTree>(tree: &T, node_id: TreeNodeID<T>)->Vec<TreeNodeID<T>>{
+    let mut path = vec![node_id];
+    while let Some(parent_id) = tree.get_node_parent_id(*path.last().unwrap()) {
+        path.push(parent_id);
+    }
+    path
+}
+
 pub trait RootedPhylogeneticDiversity: RootedWeightedTree + Clusters
 where 
-    <Self as RootedTree>::Node: RootedWeightedNode
+    <Self as RootedTree>::Node: RootedWeightedNode + RootedZetaNode
 {
+    /// Returns the length of the edge into every node (indexed by NodeID) after
+    /// applying a policy for missing branch lengths, along with the number of
+    /// non-root edges that had no length.
+    fn resolve_edge_lengths(
+        &self,
+        policy: MissingLengthPolicy,
+    ) -> Result<(Vec<TreeNodeWeight<Self>>, usize), PDError>;
+
+    /// Adds the weight of every non-root node to the length of the edge into it (or
+    /// subtracts it, for costs). A node is spanned by a taxa set exactly when the edge
+    /// into it is, so every PD computation then counts the weights of the nodes spanned.
+    /// The root is spanned by every nonempty set, so its weight does not change which
+    /// sets are optimal and is left out. Edges whose weighted length is not positive
+    /// are not counted when normalizing.
+    fn apply_node_weights(
+        &self,
+        edge_lengths: &mut [TreeNodeWeight<Self>],
+        policy: NodeWeightPolicy,
+    );
+
+    /// Computes optimal PD values and sets for every node and number of taxa.
+    /// Normalized PD divides PD by the number of positive-length edges it spans,
+    /// so zero-length edges (such as those added by `binarize_tree`) never
+    /// change the normalization. A set spans the edge into a child as soon as
+    /// it has a taxon below it, also when no taxon is selected below the
+    /// child's siblings, so the count covers exactly the edges of its rooted PD.
+    /// Unary nodes are carried through, with the path through them counted as a
+    /// single edge. Sets of equal value are chosen between with the
+    /// tie-breaking rule, independently of child order.
+    ///
+    /// Normalized PD is optimized over the unions of optimal PD sets of the
+    /// children of a node. Internal nodes below zero-length, uncounted edges
+    /// only split a polytomy, so the children of the whole polytomy are merged
+    /// at its top, which makes the values at the root and at the top of every
+    /// polytomy independent of how polytomies are resolved. Values at the split
+    /// nodes themselves still depend on the resolution.
     fn compute_dp_table(
         &self,
         op: Ordering,
+        edge_lengths: &[TreeNodeWeight<Self>],
+        tie_break: TieBreak,
     ) -> (
         Vec<Vec<(TreeNodeWeight<Self>, u32)>>,
         Vec<Vec<Vec<TreeNodeID<Self>>>>,
@@ -104,29 +395,87 @@ where
 
     fn compute_norm_min(
         &self,
+        edge_lengths: &[TreeNodeWeight<Self>],
+        tie_break: TieBreak,
     ) -> (
         Vec<Vec<(TreeNodeWeight<Self>, u32)>>,
         Vec<Vec<Vec<TreeNodeID<Self>>>>,
         Vec<Vec<(TreeNodeWeight<Self>, u32)>>,
         Vec<Vec<Vec<TreeNodeID<Self>>>>,
     ) {
-        self.compute_dp_table(Ordering::Less)
+        self.compute_dp_table(Ordering::Less, edge_lengths, tie_break)
     }
 
     fn compute_norm_max(
         &self,
+        edge_lengths: &[TreeNodeWeight<Self>],
+        tie_break: TieBreak,
     ) -> (
         Vec<Vec<(TreeNodeWeight<Self>, u32)>>,
         Vec<Vec<Vec<TreeNodeID<Self>>>>,
         Vec<Vec<(TreeNodeWeight<Self>, u32)>>,
         Vec<Vec<Vec<TreeNodeID<Self>>>>,
     ) {
-        self.compute_dp_table(Ordering::Greater)
+        self.compute_dp_table(Ordering::Greater, edge_lengths, tie_break)
     }
 
     fn compute_avg(
         &self,
+        edge_lengths: &[TreeNodeWeight<Self>],
     ) -> Vec<Vec<TreeNodeWeight<Self>>>;
+
+    /// Ranks the leaves greedily by their marginal gain in rooted PD, so that the first
+    /// k leaves of the ranking form a maxPD set for every k. Runs in O(n log n) without
+    /// computing the PD tables. Leaves of equal gain are ranked by taxon label.
+    fn compute_maxPD_ranking(
+        &self,
+        edge_lengths: &[TreeNodeWeight<Self>],
+    ) -> Vec<(TreeNodeID<Self>, TreeNodeWeight<Self>)>;
+
+    /// Computes, for every number of additional taxa, the largest gain in PD over a
+    /// protected set of leaves and the taxa achieving it. Edges spanned by the
+    /// protected set are already paid for, and protected leaves are never selected.
+    fn compute_complement(
+        &self,
+        edge_lengths: &[TreeNodeWeight<Self>],
+        protected: &[TreeNodeID<Self>],
+        tie_break: TieBreak,
+    ) -> Vec<(TreeNodeWeight<Self>, Vec<TreeNodeID<Self>>)>;
+
+    /// Computes, for every number of taxa, the largest PD plus the total value of the
+    /// selected leaves and the taxa achieving it. `leaf_values` is indexed by NodeID,
+    /// so each leaf contributes its value as a leaf-level term of the additive DP.
+    fn compute_valued_maxPD(
+        &self,
+        edge_lengths: &[TreeNodeWeight<Self>],
+        leaf_values: &[TreeNodeWeight<Self>],
+        tie_break: TieBreak,
+    ) -> Vec<(TreeNodeWeight<Self>, Vec<TreeNodeID<Self>>)>;
+
+    /// Computes a set of taxa maximizing the PD that remains after an adversary removes
+    /// any `removals` of them, along with that worst-case PD. Every subtree keeps the
+    /// non-dominated vectors of worst-case PD after each number of removals, which is
+    /// exact for up to `ROBUST_EXACT_MAX_REMOVALS` removals. Beyond that, only the
+    /// `ROBUST_BEAM_WIDTH` vectors with the best worst-case PD are kept per subtree, so
+    /// the result is a heuristic lower bound.
+    fn compute_robust_maxPD(
+        &self,
+        edge_lengths: &[TreeNodeWeight<Self>],
+        num_taxa: usize,
+        removals: usize,
+        tie_break: TieBreak,
+    ) -> (TreeNodeWeight<Self>, Vec<TreeNodeID<Self>>);
+
+    /// Computes, for every number of taxa, the taxa sets at the root on the Pareto
+    /// front between PD and the number of positive-length edges they span. With
+    /// `Ordering::Greater` PD is maximized and edges minimized, with `Ordering::Less`
+    /// PD is minimized and edges maximized. Fronts are sorted by PD, best last.
+    fn compute_pareto_front(
+        &self,
+        op: Ordering,
+        edge_lengths: &[TreeNodeWeight<Self>],
+        tie_break: TieBreak,
+    ) -> Vec<Vec<(TreeNodeWeight<Self>, u32, Vec<TreeNodeID<Self>>)>>;
 }
 
 pub trait UnrootedPhylogeneticDiversity: RootedWeightedTree + Clusters
@@ -190,34 +539,329 @@ pub fn pascal_triangle(norm: u32)->Vec<Vec<u32>>{
     pt
 }
 
-/// Arbitrarily binarize a non-binary tree
+/// Values within this many machine epsilons of each other, relative to their size,
+/// are tied and broken by the `TieBreak` rule
+pub const TIE_ULPS: u32 = 64;
+
+/// Largest number of removals for which robust maxPD is computed exactly
+pub const ROBUST_EXACT_MAX_REMOVALS: usize = 2;
+
+/// Number of candidate sets kept per subtree and number of taxa when robust maxPD is
+/// computed heuristically
+pub const ROBUST_BEAM_WIDTH: usize = 16;
+
+/// PD statistics of the clade below an internal node
+#[derive(Debug, Clone, PartialEq)]
+pub struct CladeReport<W> {
+    /// NodeID of the clade root
+    pub node_id: usize,
+    /// Name of the clade root, or `mrca(A|B)` for two leaves whose MRCA is the clade root
+    pub label: String,
+    /// Number of leaves in the clade
+    pub size: usize,
+    /// Total branch length within the clade
+    pub total_length: W,
+    /// minPD within the clade for each requested number of taxa
+    pub minPD: Vec<W>,
+    /// maxPD within the clade for each requested number of taxa
+    pub maxPD: Vec<W>,
+    /// avgPD within the clade for each requested number of taxa
+    pub avgPD: Vec<W>,
+    /// Minimum generalized PD of the clade, if it has a subset of at least three taxa with non-zero PD
+    pub min_genPD: Option<W>,
+    /// Maximum generalized PD of the clade, if it has a subset of at least three taxa with non-zero PD
+    pub max_genPD: Option<W>,
+}
+
+/// A taxa set on the Pareto front between PD and the number of edges it spans
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParetoPoint<W> {
+    /// PD of the taxa set
+    pub pd: W,
+    /// Number of positive-length edges spanned by the taxa set
+    pub num_edges: u32,
+    /// NodeIDs of the taxa in the set
+    pub taxa: Vec<usize>,
+}
+
+/// A taxa set maximizing PD plus a weighted sum of taxon values
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValuedSelection<W> {
+    /// Weight of the taxon values against PD
+    pub lambda: W,
+    /// PD plus `lambda` times the total value of the taxa set
+    pub objective: W,
+    /// PD of the taxa set
+    pub pd: W,
+    /// Total value of the taxa in the set
+    pub value: W,
+    /// NodeIDs of the taxa in the set
+    pub taxa: Vec<usize>,
+}
+
+/// A taxa set maximizing PD plus a weighted sum of taxon values for every weight in an
+/// interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValuedInterval<W> {
+    /// Smallest weight of the taxon values for which the set is optimal
+    pub lambda_min: W,
+    /// Largest weight of the taxon values for which the set is optimal
+    pub lambda_max: W,
+    /// The set, with its objective at `lambda_min`
+    pub selection: ValuedSelection<W>,
+}
+
+/// Strategy used to resolve polytomies into binary splits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveStrategy {
+    /// Split off one child at a time, producing a caterpillar
+    Caterpillar,
+    /// Recursively split the children into halves
+    Balanced,
+    /// Uniformly random binary resolution, seeded for reproducibility
+    Random(u64),
+}
+
+/// Binarize a non-binary tree by resolving polytomies into caterpillars
 pub fn binarize_tree(tree: &mut PhyloTree){
-    let mut stack = tree.postord_ids(tree.get_root_id()).collect::<VecDeque<_>>();
-    loop{
-        let n_id=stack.pop_front();
-        match n_id{
-            Some(node_id) => {
-                let node_children = tree.get_node_children_ids(node_id).collect_vec();
-                match node_children.len().cmp(&3){
-                    Ordering::Less => {continue;},
-                    _ => {
-                        let node_children_ids = tree.get_node_children_ids(node_id).collect_vec();
-                        let child1 = node_children_ids[0];
-                        let child2 = node_children_ids[1];
-                        let split_node_id = tree.next_id();
-                        let split_node = Node::new(split_node_id);
-                        // added split vertex to edge into first child
-                        tree.split_edge((node_id, child1), split_node);
-                        // deleted edge into second child
-                        tree.delete_edge(node_id, child2);
-                        // added edge from split vertex to second child
-                        tree.set_child(split_node_id, child2);
-                        // add node_id back to stack
-                        stack.push_front(node_id);
+    resolve_polytomies(tree, ResolveStrategy::Caterpillar);
+}
+
+/// Binarize a non-binary tree using the provided strategy. Split edges have length zero.
+pub fn resolve_polytomies(tree: &mut PhyloTree, strategy: ResolveStrategy){
+    match strategy {
+        ResolveStrategy::Caterpillar => resolve_polytomies_with(tree, caterpillar_splits),
+        ResolveStrategy::Balanced => resolve_polytomies_with(tree, |m| {
+            let mut splits = vec![];
+            balanced_splits(0..m, m, &mut splits);
+            splits
+        }),
+        ResolveStrategy::Random(seed) => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            resolve_polytomies_with(tree, |m| random_splits(m, &mut rng));
+        },
+    }
+}
+
+/// Returns independent uniformly random binary resolutions of a tree, drawn from a
+/// single random number generator seeded with `seed`
+pub fn random_resolutions(tree: &PhyloTree, replicates: usize, seed: u64)->Vec<PhyloTree>{
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..replicates)
+        .map(|_| {
+            let mut resolved_tree = tree.clone();
+            resolve_polytomies_with(&mut resolved_tree, |m| random_splits(m, &mut rng));
+            resolved_tree
+        })
+        .collect_vec()
+}
+
+/// Average a topology-dependent statistic over random binary resolutions of a tree
+pub fn mean_over_resolutions(
+    tree: &PhyloTree,
+    replicates: usize,
+    seed: u64,
+    mut statistic: impl FnMut(&PhyloTree)->f32,
+)->f32{
+    let values = random_resolutions(tree, replicates, seed)
+        .iter()
+        .map(&mut statistic)
+        .collect_vec();
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+/// Parses a Newick string whose nodes may carry a weight in a comment after their label
+/// or branch length, as `A[&weight=2.5]:1`, `A:1[&weight=2.5]` or `(A,B)[2.5]:1`. With
+/// a `key`, the value of that key in a comment becomes the node weight; without one, a
+/// bare number does. Comments are removed from the labels, and quoted labels are kept
+/// as they are.
+pub fn from_annotated_newick(newick: &[u8], key: Option<&str>) -> std::io::Result<PhyloTree>{
+    // the Newick parser does not know comments, so each one is moved in front of the
+    // branch length into the label, between control characters that labels do not
+    // contain and with the characters the parser splits on replaced
+    const COMMENT_START: char = '\u{2}';
+    const COMMENT_END: char = '\u{3}';
+    let mut newick_str = String::new();
+    let mut length_start = None;
+    let mut quoted = false;
+    let newick = String::from_utf8_lossy(newick);
+    let mut chars = newick.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // an escaped quote ('') closes and reopens the label
+            '\'' => {
+                quoted = !quoted;
+                newick_str.push(c);
+            }
+            _ if quoted => newick_str.push(c),
+            '[' => {
+                let comment = chars
+                    .by_ref()
+                    .take_while(|c| *c != ']')
+                    .map(|c| if "(),:;".contains(c) { '|' } else { c })
+                    .collect::<String>();
+                let marker = format!("{}{}{}", COMMENT_START, comment, COMMENT_END);
+                match length_start.as_mut() {
+                    Some(start) => {
+                        newick_str.insert_str(*start, &marker);
+                        *start += marker.len();
                     }
-                }                
+                    None => newick_str.push_str(&marker),
+                }
+            }
+            ':' => {
+                length_start = Some(newick_str.len());
+                newick_str.push(c);
+            }
+            '(' | ')' | ',' | ';' => {
+                length_start = None;
+                newick_str.push(c);
+            }
+            _ => newick_str.push(c),
+        }
+    }
+    let mut tree = PhyloTree::from_newick(newick_str.as_bytes())?;
+    for node_id in tree.get_node_ids().collect_vec() {
+        let Some((label, comments)) = tree.get_node_taxa(node_id).and_then(|x| x.split_once(COMMENT_START)) else {
+            continue;
+        };
+        let label = label.to_string();
+        let comments = comments
+            .split([COMMENT_START, COMMENT_END])
+            .map(|comment| comment.trim_start_matches('&'))
+            .filter(|comment| !comment.is_empty())
+            .collect_vec();
+        let value = match key {
+            None => comments.into_iter().find(|comment| comment.parse::<f32>().is_ok()).map(str::to_string),
+            Some(key) => comments
+                .into_iter()
+                .flat_map(|comment| comment.split('|'))
+                .filter_map(|entry| entry.split_once('='))
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string()),
+        };
+        if let Some(value) = value {
+            let weight = value.parse::<f32>().map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid node weight {:?} for {:?}", value, label),
+                )
+            })?;
+            tree.get_node_mut(node_id).unwrap().set_zeta(Some(weight));
+        }
+        tree.get_node_mut(node_id).unwrap().set_taxa((!label.is_empty()).then_some(label));
+    }
+    Ok(tree)
+}
+
+/// Makes every labelled internal node a selectable taxon (a sampled ancestor) by
+/// moving its label onto a new zero-length tip below it. Returns the number of tips
+/// added.
+pub fn add_sampled_ancestor_tips(tree: &mut PhyloTree)->usize{
+    let ancestor_ids = tree
+        .get_node_ids()
+        .filter(|x| !tree.is_leaf(*x) && tree.get_node_taxa(*x).is_some())
+        .collect_vec();
+    for node_id in ancestor_ids.iter() {
+        let taxa = tree.get_node_taxa_cloned(*node_id);
+        tree.get_node_mut(*node_id).unwrap().set_taxa(None);
+        let mut tip = Node::new(tree.next_id());
+        tip.set_weight(Some(0.0));
+        tip.set_taxa(taxa);
+        tree.add_child(*node_id, tip);
+    }
+    ancestor_ids.len()
+}
+
+/// Resolves every polytomy with the binary splits over its m children returned by
+/// `splits`, listed so that every split follows its parts
+fn resolve_polytomies_with(tree: &mut PhyloTree, mut splits: impl FnMut(usize)->Vec<(usize, usize)>){
+    for node_id in tree.postord_ids(tree.get_root_id()).collect_vec() {
+        let node_children = tree.get_node_children_ids(node_id).collect_vec();
+        if node_children.len() < 3 {
+            continue;
+        }
+        let node_splits = splits(node_children.len());
+        let mut parts = node_children;
+        // the last split is the polytomy node itself
+        for (a, b) in node_splits.into_iter().take(parts.len()-2) {
+            let split_node_id = tree.next_id();
+            let mut split_node = Node::new(split_node_id);
+            // split edges carry no length
+            split_node.set_weight(Some(0.0));
+            tree.add_child(node_id, split_node);
+            for part in [parts[a], parts[b]] {
+                tree.delete_edge(node_id, part);
+                tree.set_child(split_node_id, part);
+            }
+            parts.push(split_node_id);
+        }
+    }
+}
+
+/// Splits of a caterpillar over m parts, where index m+i refers to the i'th split
+fn caterpillar_splits(m: usize)->Vec<(usize, usize)>{
+    let mut splits = vec![(0, 1)];
+    for i in 2..m {
+        splits.push((m+i-2, i));
+    }
+    splits
+}
+
+/// Splits of a balanced tree over a range of parts, returning the index of the range's root
+fn balanced_splits(parts: std::ops::Range<usize>, m: usize, splits: &mut Vec<(usize, usize)>)->usize{
+    if parts.len() == 1 {
+        return parts.start;
+    }
+    let mid = parts.start + parts.len()/2;
+    let left = balanced_splits(parts.start..mid, m, splits);
+    let right = balanced_splits(mid..parts.end, m, splits);
+    splits.push((left, right));
+    m + splits.len() - 1
+}
+
+/// Splits of a binary tree over m parts drawn uniformly from all rooted binary topologies
+fn random_splits(m: usize, rng: &mut StdRng)->Vec<(usize, usize)>{
+    // children of each split, and the parent of every part and split
+    let mut children = vec![[0, 1]];
+    let mut parent: Vec<Option<usize>> = vec![Some(m), Some(m)];
+    parent.extend(vec![None; m-2]);
+    parent.push(None);
+    let mut root = m;
+    for i in 2..m {
+        // attach part i to the edge above a uniformly chosen part or split
+        let num_placed = 2*i-1;
+        let choice = rng.gen_range(0..num_placed);
+        let target = if choice < i { choice } else { m + choice - i };
+        let new_split = m + children.len();
+        children.push([target, i]);
+        parent.push(parent[target]);
+        match parent[target] {
+            Some(p) => {
+                let slot = children[p-m].iter().position(|x| *x == target).unwrap();
+                children[p-m][slot] = new_split;
             },
-            None => {break}
+            None => root = new_split,
         }
+        parent[target] = Some(new_split);
+        parent[i] = Some(new_split);
     }
-}
\ No newline at end of file
+    // list splits in post-order so that every split follows its parts
+    let mut order = vec![];
+    let mut stack = vec![(root, false)];
+    while let Some((id, expanded)) = stack.pop() {
+        if id < m {
+            continue;
+        }
+        if expanded {
+            order.push(id);
+        } else {
+            stack.push((id, true));
+            stack.push((children[id-m][1], false));
+            stack.push((children[id-m][0], false));
+        }
+    }
+    let position = order.iter().enumerate().map(|(pos, id)| (*id, m+pos)).collect::<HashMap<_,_>>();
+    let relabel = |x: usize| if x < m { x } else { position[&x] };
+    order.into_iter().map(|id| (relabel(children[id-m][0]), relabel(children[id-m][1]))).collect_vec()
+}
@@ -0,0 +1,277 @@
+use std::collections::HashSet;
+
+use phylo::prelude::*;
+
+use super::error::PDError;
+use super::TreePD;
+
+/// Default number of search nodes the branch-and-bound explores before it stops and
+/// reports the best selection found so far
+pub const RESERVE_MAX_SEARCH_NODES: usize = 100_000;
+
+/// Sites that can be selected as reserves, each protecting a set of taxa at a cost
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sites<W> {
+    /// NodeIDs of the leaves protected by every site
+    taxa: Vec<Vec<usize>>,
+    /// Cost of every site
+    costs: Vec<W>,
+}
+
+/// Sites selected as reserves
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReserveSelection<W> {
+    /// PD of the union of the taxa protected by the sites
+    pub pd: W,
+    /// Total cost of the sites
+    pub cost: W,
+    /// Indices of the selected sites
+    pub sites: Vec<usize>,
+    /// Whether the selection is proven optimal. Heuristic selections, and exact
+    /// searches that were cut off, are not.
+    pub exact: bool,
+}
+
+impl<W:EdgeWeight> Sites<W> {
+    /// Builds the sites from the taxon labels each site protects and its cost. Use unit
+    /// costs and a budget of m to select m sites. The approximation guarantee of the
+    /// greedy selection and the bound of the exact search require PD to grow as sites
+    /// are added, so negative edge lengths are rejected.
+    pub fn new<T:NodeTaxa,Z:NodeWeight>(
+        tree_pd: &TreePD<'_,T,W,Z>,
+        sites: &[(Vec<T>, W)],
+    ) -> Result<Self, PDError> {
+        tree_pd.check_non_negative_lengths()?;
+        let taxa = sites
+            .iter()
+            .map(|(taxa, _)| taxa.iter().map(|taxon| tree_pd.get_taxon_id(taxon)).collect())
+            .collect::<Result<Vec<_>, _>>()?;
+        let costs = sites.iter().map(|(_, cost)| *cost).collect();
+        Ok(Sites { taxa, costs })
+    }
+
+    /// Returns the number of sites
+    pub fn num_sites(&self) -> usize {
+        self.taxa.len()
+    }
+
+    /// Returns the PD of the union of the taxa protected by a set of sites
+    pub fn get_sites_PD<T:NodeTaxa,Z:NodeWeight>(&self, tree_pd: &TreePD<'_,T,W,Z>, sites: &[usize]) -> W {
+        let taxa = sites.iter().flat_map(|site| self.taxa[*site].iter().copied()).collect::<Vec<_>>();
+        tree_pd.taxa_set_pd(&taxa)
+    }
+
+    /// Selects sites within a budget greedily, each time adding the affordable site
+    /// with the largest gain in PD per unit cost, and returns the better of this and
+    /// the best single affordable site. PD of a union of taxa is submodular in the
+    /// sites, so with unit costs the selection is within a factor (1-1/e) of optimal,
+    /// and within (1-1/e)/2 for general costs.
+    pub fn get_greedy_reserves<T:NodeTaxa,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        budget: W,
+    ) -> ReserveSelection<W> {
+        let mut covered = vec![false; tree_pd.edge_lengths.len()];
+        let mut chosen: Vec<usize> = vec![];
+        let mut cost = W::zero();
+        loop {
+            let mut best: Option<(W, usize)> = None;
+            for site in 0..self.num_sites() {
+                if chosen.contains(&site) || cost + self.costs[site] > budget {
+                    continue;
+                }
+                let gain = self.gain(tree_pd, &covered, site);
+                let ratio = match self.costs[site] > W::zero() {
+                    true => gain / self.costs[site],
+                    false => W::infinity(),
+                };
+                if gain > W::zero() && best.is_none_or(|(best_ratio, _)| ratio > best_ratio) {
+                    best = Some((ratio, site));
+                }
+            }
+            let Some((_, site)) = best else {
+                break;
+            };
+            self.cover(tree_pd, &mut covered, site);
+            cost = cost + self.costs[site];
+            chosen.push(site);
+        }
+        let greedy = self.selection(tree_pd, chosen, false);
+        (0..self.num_sites())
+            .filter(|site| self.costs[*site] <= budget)
+            .map(|site| self.selection(tree_pd, vec![site], false))
+            .fold(greedy, |best, single| if single.pd > best.pd { single } else { best })
+    }
+
+    /// Improves the greedy selection by local search, repeatedly applying the move
+    /// adding a site, or swapping a selected site for another, that increases PD the
+    /// most while staying within the budget
+    pub fn get_local_search_reserves<T:NodeTaxa,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        budget: W,
+    ) -> ReserveSelection<W> {
+        let mut current = self.get_greedy_reserves(tree_pd, budget);
+        loop {
+            let unchosen = (0..self.num_sites()).filter(|site| !current.sites.contains(site)).collect::<Vec<_>>();
+            let additions = unchosen.iter().map(|site| [current.sites.clone(), vec![*site]].concat());
+            let swaps = (0..current.sites.len()).flat_map(|i| {
+                let current = &current;
+                unchosen.iter().map(move |site| {
+                    let mut sites = current.sites.clone();
+                    sites[i] = *site;
+                    sites
+                })
+            });
+            let best = additions
+                .chain(swaps)
+                .filter(|sites| sites.iter().map(|site| self.costs[*site]).sum::<W>() <= budget)
+                .map(|sites| self.selection(tree_pd, sites, false))
+                .fold(None, |best: Option<ReserveSelection<W>>, candidate| match &best {
+                    Some(best_selection) if candidate.pd <= best_selection.pd => best,
+                    _ => Some(candidate),
+                });
+            match best {
+                Some(best) if best.pd > current.pd => current = best,
+                _ => return current,
+            }
+        }
+    }
+
+    /// Returns an optimal selection of sites within a budget by branch-and-bound,
+    /// starting from the local search selection. A partial selection is bounded by
+    /// its PD plus the largest total gain of the undecided sites that fits in the
+    /// remaining budget, allowing fractions of sites, which is valid since the gain of
+    /// a set of sites is at most the sum of their gains. If more than
+    /// `max_search_nodes` search nodes are needed, the best selection found so far is
+    /// returned and marked as not exact.
+    pub fn get_exact_reserves<T:NodeTaxa,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        budget: W,
+        max_search_nodes: usize,
+    ) -> ReserveSelection<W> {
+        // decide the sites with the largest PD first
+        let mut order = (0..self.num_sites()).collect::<Vec<_>>();
+        let site_pds = order.iter().map(|site| self.get_sites_PD(tree_pd, &[*site])).collect::<Vec<_>>();
+        order.sort_by(|a, b| site_pds[*b].partial_cmp(&site_pds[*a]).unwrap());
+        let mut search = Search {
+            sites: self,
+            tree_pd,
+            order,
+            budget,
+            chosen: vec![],
+            best: self.get_local_search_reserves(tree_pd, budget),
+            nodes_left: max_search_nodes,
+        };
+        let exact = search.branch(0, W::zero());
+        ReserveSelection { exact, ..search.best }
+    }
+
+    fn selection<T:NodeTaxa,Z:NodeWeight>(
+        &self,
+        tree_pd: &TreePD<'_,T,W,Z>,
+        mut sites: Vec<usize>,
+        exact: bool,
+    ) -> ReserveSelection<W> {
+        sites.sort();
+        ReserveSelection {
+            pd: self.get_sites_PD(tree_pd, &sites),
+            cost: sites.iter().map(|site| self.costs[*site]).sum(),
+            sites,
+            exact,
+        }
+    }
+
+    /// Returns the PD a site adds to the nodes already covered
+    fn gain<T:NodeTaxa,Z:NodeWeight>(&self, tree_pd: &TreePD<'_,T,W,Z>, covered: &[bool], site: usize) -> W {
+        let mut visited = HashSet::new();
+        let mut gain = W::zero();
+        for leaf_id in self.taxa[site].iter() {
+            let path = std::iter::successors(Some(*leaf_id), |x| tree_pd.tree.get_node_parent_id(*x));
+            for node_id in path.take_while(|x| !covered[*x] && *x != tree_pd.tree.get_root_id()) {
+                if !visited.insert(node_id) {
+                    break;
+                }
+                gain = gain + tree_pd.edge_lengths[node_id];
+            }
+        }
+        gain
+    }
+
+    fn cover<T:NodeTaxa,Z:NodeWeight>(&self, tree_pd: &TreePD<'_,T,W,Z>, covered: &mut [bool], site: usize) {
+        for leaf_id in self.taxa[site].iter() {
+            let mut node_id = *leaf_id;
+            while !covered[node_id] {
+                covered[node_id] = true;
+                match tree_pd.tree.get_node_parent_id(node_id) {
+                    Some(parent_id) => node_id = parent_id,
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+/// State of the branch-and-bound search for an optimal selection of sites
+struct Search<'a, 'b, T:NodeTaxa, W:EdgeWeight, Z:NodeWeight> {
+    sites: &'a Sites<W>,
+    tree_pd: &'a TreePD<'b,T,W,Z>,
+    /// Order in which sites are decided
+    order: Vec<usize>,
+    budget: W,
+    chosen: Vec<usize>,
+    best: ReserveSelection<W>,
+    nodes_left: usize,
+}
+
+impl<T:NodeTaxa, W:EdgeWeight, Z:NodeWeight> Search<'_, '_, T, W, Z> {
+    /// Explores all selections extending the chosen sites with sites from
+    /// `order[depth..]`. Returns false if the search was cut off.
+    fn branch(&mut self, depth: usize, cost: W) -> bool {
+        if self.nodes_left == 0 {
+            return false;
+        }
+        self.nodes_left -= 1;
+        let pd = self.sites.get_sites_PD(self.tree_pd, &self.chosen);
+        if pd > self.best.pd {
+            self.best = self.sites.selection(self.tree_pd, self.chosen.clone(), false);
+        }
+        let mut covered = vec![false; self.tree_pd.edge_lengths.len()];
+        for site in self.chosen.iter() {
+            self.sites.cover(self.tree_pd, &mut covered, *site);
+        }
+        // fractional knapsack over the gains of the undecided sites
+        let mut candidates = self.order[depth..]
+            .iter()
+            .filter(|site| cost + self.sites.costs[**site] <= self.budget)
+            .map(|site| (self.sites.gain(self.tree_pd, &covered, *site), self.sites.costs[*site]))
+            .filter(|(gain, _)| *gain > W::zero())
+            .collect::<Vec<_>>();
+        candidates.sort_by(|(gain_a, cost_a), (gain_b, cost_b)| (*gain_b * *cost_a).partial_cmp(&(*gain_a * *cost_b)).unwrap());
+        let mut bound = pd;
+        let mut remaining = self.budget - cost;
+        for (gain, site_cost) in candidates {
+            if site_cost <= remaining {
+                bound = bound + gain;
+                remaining = remaining - site_cost;
+            } else {
+                bound = bound + gain * remaining / site_cost;
+                break;
+            }
+        }
+        if bound <= self.best.pd || depth == self.order.len() {
+            return true;
+        }
+        let site = self.order[depth];
+        if cost + self.sites.costs[site] <= self.budget {
+            self.chosen.push(site);
+            let complete = self.branch(depth + 1, cost + self.sites.costs[site]);
+            self.chosen.pop();
+            if !complete {
+                return false;
+            }
+        }
+        self.branch(depth + 1, cost)
+    }
+}
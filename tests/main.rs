@@ -1,6 +1,87 @@
 use phylo::prelude::*;
+use std::cmp::Ordering;
 use itertools::Itertools;
-use PD::pd::{phylogenetic_diversity::{binarize_tree, TreePDMap}, TreePD};
+use PD::pd::{conflicts::ConflictGraph, dependencies::DependencyNetwork, error::PDError, groups::{GroupQuota, Groups}, noahs_ark::{solve_noahs_ark, Project}, reserves::Sites, normalization::{Normalization, NormalizationKind, TaxaSetSummary}, null_model::{get_null_normPD, get_null_normPD_profile, NULL_SAMPLES}, phylogenetic_diversity::{add_sampled_ancestor_tips, binarize_tree, from_annotated_newick, mean_over_resolutions, random_resolutions, resolve_polytomies, MissingLengthPolicy, NodeWeightPolicy, ParetoPoint, PDOptions, ResolveStrategy, RootedPhylogeneticDiversity, TieBreak, TreePDMap, ROBUST_EXACT_MAX_REMOVALS}, TreePD};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Random binary tree in Newick format over the given taxa, with integer branch lengths
+fn random_newick(taxa: &[usize], rng: &mut StdRng) -> String {
+    let length = rng.gen_range(0..5);
+    match taxa.len() {
+        1 => format!("T{}:{}", taxa[0], length),
+        n => {
+            let split = rng.gen_range(1..n);
+            format!("({},{}):{}", random_newick(&taxa[..split], rng), random_newick(&taxa[split..], rng), length)
+        }
+    }
+}
+
+/// Rooted PD of a set of leaves, computed from the edges on their paths to the root
+fn rooted_pd(tree: &PhyloTree, taxa: &[usize]) -> f32 {
+    let mut edges = std::collections::HashSet::new();
+    for leaf_id in taxa {
+        let mut node_id = *leaf_id;
+        while let Some(parent_id) = tree.get_node_parent_id(node_id) {
+            edges.insert(node_id);
+            node_id = parent_id;
+        }
+    }
+    edges.into_iter().map(|x| tree.get_node(x).unwrap().get_weight().unwrap()).sum()
+}
+
+/// Random ultrametric binary tree in Newick format over the given taxa, whose root is
+/// below a node of the given height
+fn random_ultrametric_newick(taxa: &[usize], parent_height: usize, rng: &mut StdRng) -> String {
+    if taxa.len() == 1 {
+        return format!("T{}:{}", taxa[0], parent_height);
+    }
+    let height = rng.gen_range(taxa.len() - 1..parent_height);
+    let split = rng.gen_range(1..taxa.len());
+    format!(
+        "({},{}):{}",
+        random_ultrametric_newick(&taxa[..split], height, rng),
+        random_ultrametric_newick(&taxa[split..], height, rng),
+        parent_height - height,
+    )
+}
+
+/// Generalized PD by enumerating every set of at least three leaves, or None if no set
+/// has a finite, non-zero normalized PD
+fn brute_force_gen_pd(tree: &PhyloTree, norm: &impl Normalization<f32>, op: Ordering) -> Option<f32> {
+    let length = |x: usize| tree.get_node(x).unwrap().get_weight().unwrap();
+    let path = |x: usize| {
+        std::iter::successors(Some(x), |y| tree.get_node_parent_id(*y))
+            .take_while(|y| *y != tree.get_root_id())
+            .collect_vec()
+    };
+    let leaf_ids = tree.get_leaf_ids().collect_vec();
+    let tree_length = tree.get_node_ids().filter(|x| *x != tree.get_root_id()).map(length).sum();
+    let mut best: Option<f32> = None;
+    for taxa in leaf_ids.iter().copied().powerset().filter(|x| x.len() >= 3) {
+        let edges = taxa.iter().flat_map(|x| path(*x)).unique().collect_vec();
+        let mrca = tree
+            .get_node_ids()
+            .filter(|x| taxa.iter().all(|y| *y == *x || path(*y).contains(x) || *x == tree.get_root_id()))
+            .max_by_key(|x| path(*x).len())
+            .unwrap();
+        let summary = TaxaSetSummary {
+            pd: rooted_pd(tree, &taxa),
+            num_taxa: taxa.len(),
+            num_edges: edges.iter().filter(|x| length(**x) > 0.0).count() as u32,
+            mrca_clade_pd: rooted_pd(tree, &tree.get_cluster_ids(mrca).collect_vec()),
+            tree_length,
+            crown_age: taxa
+                .iter()
+                .map(|x| path(*x).into_iter().take_while(|y| *y != mrca).map(length).sum::<f32>())
+                .fold(0.0, f32::max),
+        };
+        let value = norm.normalize(&summary);
+        if value != 0.0 && value.is_finite() && best.is_none_or(|x| value.partial_cmp(&x) == Some(op)) {
+            best = Some(value);
+        }
+    }
+    best
+}
 
 #[test]
 fn binarize() {
@@ -18,35 +99,37 @@ fn pd() {
     let num_taxa = 2;
 
     dbg!(
-        tree_pd.get_minPD(num_taxa.clone()),
-        tree_pd.get_norm_minPD(num_taxa.clone()),
+        tree_pd.get_minPD(num_taxa),
+        tree_pd.get_norm_minPD(num_taxa),
         tree_pd
-            .get_minPD_taxa_set(num_taxa.clone())
+            .get_minPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         tree_pd
-            .get_norm_minPD_taxa_set(num_taxa.clone())
+            .get_norm_minPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         tree_pd.get_min_genPD(),
         tree_pd.get_min_genPD_set()
+            .unwrap()
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
     );
 
     dbg!(
-        tree_pd.get_maxPD(num_taxa.clone()),
-        tree_pd.get_norm_maxPD(num_taxa.clone()),
+        tree_pd.get_maxPD(num_taxa),
+        tree_pd.get_norm_maxPD(num_taxa),
         tree_pd
-            .get_maxPD_taxa_set(num_taxa.clone())
+            .get_maxPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         tree_pd
-            .get_norm_maxPD_taxa_set(num_taxa.clone())
+            .get_norm_maxPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         tree_pd.get_max_genPD(),
         tree_pd.get_max_genPD_set()
+            .unwrap()
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
     );
@@ -59,35 +142,37 @@ fn pd() {
     let num_taxa = 2;
 
     dbg!(
-        tree_pd.get_minPD(num_taxa.clone()),
-        tree_pd.get_norm_minPD(num_taxa.clone()),
+        tree_pd.get_minPD(num_taxa),
+        tree_pd.get_norm_minPD(num_taxa),
         tree_pd
-            .get_minPD_taxa_set(num_taxa.clone())
+            .get_minPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         tree_pd
-            .get_norm_minPD_taxa_set(num_taxa.clone())
+            .get_norm_minPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         tree_pd.get_min_genPD(),
         tree_pd.get_min_genPD_set()
+            .unwrap()
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
     );
 
     dbg!(
-        tree_pd.get_maxPD(num_taxa.clone()),
-        tree_pd.get_norm_maxPD(num_taxa.clone()),
+        tree_pd.get_maxPD(num_taxa),
+        tree_pd.get_norm_maxPD(num_taxa),
         tree_pd
-            .get_maxPD_taxa_set(num_taxa.clone())
+            .get_maxPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         tree_pd
-            .get_norm_maxPD_taxa_set(num_taxa.clone())
+            .get_norm_maxPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         tree_pd.get_max_genPD(),
         tree_pd.get_max_genPD_set()
+            .unwrap()
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
     );
@@ -102,17 +187,1143 @@ fn avg_pd() {
     let num_taxa = 2;
 
     dbg!(
-        tree_pd.get_avgPD(num_taxa.clone()),
-        tree_pd.get_maxPD(num_taxa.clone()),
+        tree_pd.get_avgPD(num_taxa),
+        tree_pd.get_maxPD(num_taxa),
         tree_pd
-            .get_maxPD_taxa_set(num_taxa.clone())
+            .get_maxPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
-        tree_pd.get_minPD(num_taxa.clone()),
+        tree_pd.get_minPD(num_taxa),
         tree_pd
-            .get_minPD_taxa_set(num_taxa.clone())
+            .get_minPD_taxa_set(num_taxa)
             .map(|x| tree.get_node_taxa(x).unwrap())
             .join(","),
         );
 
 }
+
+#[test]
+fn missing_lengths() {
+    let input_str: String = String::from("(((A:1,B):2,C:7):4,(D:1,E:2):5);");
+    let tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+
+    assert_eq!(
+        TreePD::with_missing_length_policy(&tree, MissingLengthPolicy::Error).err(),
+        Some(PDError::MissingBranchLengths { num_missing: 1 })
+    );
+
+    let tree_pd = TreePD::with_missing_length_policy(&tree, MissingLengthPolicy::Zero).unwrap();
+    assert_eq!(tree_pd.num_missing_lengths(), 1);
+    assert_eq!(tree_pd.get_maxPD(5), 22.0);
+
+    let tree_pd = TreePD::with_missing_length_policy(&tree, MissingLengthPolicy::Unit).unwrap();
+    assert_eq!(tree_pd.get_maxPD(5), 23.0);
+
+    let tree_pd = TreePD::with_missing_length_policy(&tree, MissingLengthPolicy::Mean).unwrap();
+    assert!((tree_pd.get_maxPD(5) - (22.0 + 22.0 / 7.0)).abs() < 1e-5);
+}
+
+#[test]
+fn norm_pd_resolution_invariant() {
+    let norm_pds = |tree: &PhyloTree| {
+        let tree_pd = TreePD::new(tree);
+        let mut norm_pd = (1..=tree.num_taxa())
+            .flat_map(|k| [tree_pd.get_norm_minPD(k), tree_pd.get_norm_maxPD(k)])
+            .collect_vec();
+        norm_pd.push(tree_pd.get_min_genPD().unwrap_or(f32::NAN));
+        norm_pd.push(tree_pd.get_max_genPD().unwrap_or(f32::NAN));
+        norm_pd
+    };
+    let mut rng = StdRng::seed_from_u64(3);
+    for _ in 0..20 {
+        let length = |rng: &mut StdRng| rng.gen_range(0..5);
+        let newick = format!(
+            "({},({}):2,({}):1);",
+            (0..3).map(|i| format!("T{}:{}", i, length(&mut rng))).join(","),
+            (3..7).map(|i| format!("T{}:{}", i, length(&mut rng))).join(","),
+            (7..9).map(|i| format!("T{}:{}", i, length(&mut rng))).join(","),
+        );
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let mut caterpillar = tree.clone();
+        resolve_polytomies(&mut caterpillar, ResolveStrategy::Caterpillar);
+        let expected = norm_pds(&caterpillar);
+        let mut balanced = tree.clone();
+        resolve_polytomies(&mut balanced, ResolveStrategy::Balanced);
+        assert_eq!(norm_pds(&balanced), expected, "{}", newick);
+        for seed in 0..4 {
+            for resolution in random_resolutions(&tree, 2, seed) {
+                assert_eq!(norm_pds(&resolution), expected, "{}", newick);
+            }
+        }
+    }
+
+    // a taxon set spans the edge into a child even if its sibling has no selected taxa
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    assert_eq!(tree_pd.get_norm_maxPD(2), 2.4);
+}
+
+#[test]
+fn resolve_strategies() {
+    let input_str: String = String::from("(A:1,B:2,C:7,D:3,E:4,F:6,G:5,H:8);");
+    let tree = PhyloTree::from_newick(input_str.as_bytes()).unwrap();
+    let max_depth = |tree: &PhyloTree| {
+        tree.get_leaf_ids()
+            .map(|x| RootedTree::get_node_depth(tree, x))
+            .max()
+            .unwrap()
+    };
+
+    let mut caterpillar = tree.clone();
+    resolve_polytomies(&mut caterpillar, ResolveStrategy::Caterpillar);
+    assert!(caterpillar.is_binary());
+    assert_eq!(max_depth(&caterpillar), 7);
+
+    let mut balanced = tree.clone();
+    resolve_polytomies(&mut balanced, ResolveStrategy::Balanced);
+    assert!(balanced.is_binary());
+    assert_eq!(max_depth(&balanced), 3);
+
+    let mut random = tree.clone();
+    resolve_polytomies(&mut random, ResolveStrategy::Random(42));
+    let mut random_again = tree.clone();
+    resolve_polytomies(&mut random_again, ResolveStrategy::Random(42));
+    assert!(random.is_binary());
+    assert_eq!(random.to_newick().to_string(), random_again.to_newick().to_string());
+
+    for resolved in random_resolutions(&tree, 10, 7) {
+        assert!(resolved.is_binary());
+        assert_eq!(resolved.get_leaf_ids().len(), 8);
+    }
+    // the seed of random_resolutions seeds the draw of the first replicate
+    let newicks = |seed| random_resolutions(&tree, 2, seed).iter().map(|x| x.to_newick().to_string()).collect_vec();
+    assert_eq!(newicks(42)[0], random.to_newick().to_string());
+    assert_eq!(newicks(42), newicks(42));
+    let max_pd = TreePD::new(&caterpillar).get_maxPD(3);
+    let mean_max_pd = mean_over_resolutions(&tree, 10, 7, |x| TreePD::new(x).get_maxPD(3));
+    assert!((mean_max_pd - max_pd).abs() < 1e-5);
+}
+
+#[test]
+fn unary_nodes() {
+    // a unary node below a polytomy, next to a unary node above a leaf
+    let unary = PhyloTree::from_newick("((A:1,B:2):3,(((C:4):2,D:1,G:2.5)H:0.5):1);".as_bytes()).unwrap();
+    let suppressed = PhyloTree::from_newick("((A:1,B:2):3,(C:6,D:1,G:2.5):1.5);".as_bytes()).unwrap();
+    let unary_pd = TreePD::new(&unary);
+    let suppressed_pd = TreePD::new(&suppressed);
+    let leaf_ids = suppressed.get_leaf_ids().collect_vec();
+    for k in 1..=5 {
+        assert_eq!(unary_pd.get_minPD(k), suppressed_pd.get_minPD(k));
+        assert_eq!(unary_pd.get_maxPD(k), suppressed_pd.get_maxPD(k));
+        assert_eq!(unary_pd.get_norm_minPD(k), suppressed_pd.get_norm_minPD(k));
+        let sets = leaf_ids.iter().copied().combinations(k).collect_vec();
+        let avg_pd = sets.iter().map(|x| rooted_pd(&suppressed, x)).sum::<f32>() / sets.len() as f32;
+        assert!((unary_pd.get_avgPD(k) - avg_pd).abs() < 1e-5);
+        assert!((suppressed_pd.get_avgPD(k) - avg_pd).abs() < 1e-5);
+        assert_eq!(
+            unary_pd.get_maxPD_taxa_set(k).map(|x| unary.get_node_taxa(x).unwrap()).sorted().collect_vec(),
+            suppressed_pd.get_maxPD_taxa_set(k).map(|x| suppressed.get_node_taxa(x).unwrap()).sorted().collect_vec(),
+        );
+    }
+}
+
+#[test]
+fn sampled_ancestors() {
+    let mut tree = PhyloTree::from_newick("((A:1,B:2)X:3,C:4);".as_bytes()).unwrap();
+    assert_eq!(add_sampled_ancestor_tips(&mut tree), 1);
+    assert_eq!(tree.get_leaf_ids().len(), 4);
+    let tree_pd = TreePD::new(&tree);
+    let taxa_set = |set: Vec<usize>| set.into_iter().map(|x| tree.get_node_taxa(x).unwrap()).sorted().join(",");
+
+    assert_eq!(tree_pd.get_minPD(1), 3.0);
+    assert_eq!(taxa_set(tree_pd.get_minPD_taxa_set(1).collect_vec()), "X");
+    assert_eq!(tree_pd.get_minPD(2), 4.0);
+    assert_eq!(taxa_set(tree_pd.get_minPD_taxa_set(2).collect_vec()), "A,X");
+    assert_eq!(tree_pd.get_maxPD(2), 9.0);
+    assert_eq!(tree_pd.get_maxPD(4), 10.0);
+    assert!((tree_pd.get_avgPD(1) - 4.0).abs() < 1e-5);
+    // pairs AB, AX, BX, AC, BC, XC have PD 6, 4, 5, 8, 9, 7
+    assert!((tree_pd.get_avgPD(2) - 6.5).abs() < 1e-5);
+}
+
+#[test]
+fn clade_queries() {
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let clade_id = tree_pd.get_clade_id(&["C".to_string(), "D".to_string()]).unwrap();
+    assert_eq!(tree_pd.get_maxPD_node(clade_id, 2), 7.0);
+    assert_eq!(tree_pd.get_maxPD_node(clade_id, 10), 7.5);
+    assert_eq!(tree_pd.get_minPD_node(clade_id, 2), 3.5);
+    assert_eq!(
+        tree_pd.get_maxPD_taxa_set_node(clade_id, 2).map(|x| tree.get_node_taxa(x).unwrap()).sorted().collect_vec(),
+        vec!["C", "D"],
+    );
+    let leaf_id = tree_pd.get_clade_id(&["A".to_string()]).unwrap();
+    assert_eq!(tree_pd.get_maxPD_node(leaf_id, 3), 0.0);
+    assert_eq!(tree_pd.get_maxPD(10), 14.5);
+    // a clade of two taxa has no generalized PD
+    let cherry_id = tree_pd.get_clade_id(&["A".to_string(), "B".to_string()]).unwrap();
+    assert_eq!(tree_pd.get_max_genPD_node(cherry_id), None);
+    assert_eq!(tree_pd.get_min_genPD_node(cherry_id), None);
+    assert!(tree_pd.get_max_genPD_set_node(cherry_id).is_none());
+    assert_eq!(tree_pd.get_max_genPD_node(clade_id), Some(7.5 / 4.0));
+    let cherry = PhyloTree::from_newick("(A:1,B:2);".as_bytes()).unwrap();
+    assert_eq!(TreePD::new(&cherry).get_max_genPD_names(), Ok(None));
+    assert_eq!(
+        tree_pd.get_clade_id(&["C".to_string(), "X".to_string()]),
+        Err(PDError::UnknownTaxon("X".to_string())),
+    );
+}
+
+#[test]
+fn clade_reports() {
+    let tree = PhyloTree::from_newick("((A:1,B:2)ab:3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let reports = tree_pd.clade_reports(&[2, 3]).collect_vec();
+    assert_eq!(reports.len(), 4);
+    assert_eq!(reports.iter().map(|x| x.label.as_str()).collect_vec(), vec!["mrca(A|D)", "ab", "mrca(C|D)", "mrca(C|E)"]);
+    let clade = reports.iter().find(|x| x.label == "mrca(C|D)").unwrap();
+    assert_eq!(clade.node_id, tree_pd.get_clade_id(&["C".to_string(), "D".to_string()]).unwrap());
+    // clades are looked up by leaf labels only, and duplicate labels are detected
+    assert_eq!(tree_pd.get_clade_id(&["ab".to_string()]), Err(PDError::UnknownTaxon("ab".to_string())));
+    let duplicated = PhyloTree::from_newick("((A:1,A:2):3,B:1);".as_bytes()).unwrap();
+    assert_eq!(
+        TreePD::new(&duplicated).get_clade_id(&["B".to_string()]),
+        Err(PDError::DuplicateTaxon("A".to_string())),
+    );
+    assert_eq!(clade.size, 3);
+    assert_eq!(clade.total_length, 7.5);
+    assert_eq!(clade.maxPD, vec![7.0, 7.5]);
+    assert_eq!(clade.minPD, vec![3.5, 7.5]);
+    assert_eq!(clade.max_genPD, Some(1.875));
+    let cherry = reports.iter().find(|x| x.label == "ab").unwrap();
+    assert_eq!((cherry.min_genPD, cherry.max_genPD), (None, None));
+    assert_eq!(reports[0].total_length, tree_pd.get_maxPD(5));
+}
+
+#[test]
+fn taxon_names() {
+    let mut tree = PhyloTree::from_newick("((A:1,B:2):3,(C:4,E:0.5,D:1):1);".as_bytes()).unwrap();
+    binarize_tree(&mut tree);
+    let tree_pd = TreePD::new(&tree);
+    assert_eq!(tree_pd.get_maxPD_taxa_names(2).unwrap().into_iter().sorted().collect_vec(), vec!["B", "C"]);
+    assert_eq!(tree_pd.get_minPD_taxa_names(5).unwrap().len(), 5);
+    let leaf_id = tree_pd.get_taxon_id(&"C".to_string()).unwrap();
+    assert_eq!(tree.get_node_taxa(leaf_id).unwrap(), "C");
+    let clade_id = tree_pd.get_clade_id(&["C".to_string(), "D".to_string()]).unwrap();
+    assert_eq!(tree_pd.get_maxPD_node(clade_id, 3), 5.5);
+    assert_eq!(tree_pd.get_taxon_id(&"X".to_string()), Err(PDError::UnknownTaxon("X".to_string())));
+
+    let duplicated = PhyloTree::from_newick("((A:1,B:2):3,(A:4,D:1):1);".as_bytes()).unwrap();
+    let duplicated_pd = TreePD::new(&duplicated);
+    assert_eq!(duplicated_pd.get_maxPD_taxa_names(2), Err(PDError::DuplicateTaxon("A".to_string())));
+
+    let unlabelled = PhyloTree::from_newick("((A:1,:2):3,(:4,D:1):1);".as_bytes()).unwrap();
+    let unlabelled_pd = TreePD::new(&unlabelled);
+    assert_eq!(unlabelled_pd.get_taxon_id(&"A".to_string()), Err(PDError::UnlabelledLeaves { num_unlabelled: 2 }));
+}
+
+#[test]
+fn tie_breaking() {
+    let max_names = |newick: &str, tie_break: TieBreak, num_taxa: usize| {
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let options = PDOptions { tie_break, ..Default::default() };
+        let tree_pd = TreePD::with_options(&tree, options).unwrap();
+        tree_pd.get_maxPD_taxa_names(num_taxa).unwrap().into_iter().sorted().collect_vec()
+    };
+    for newick in ["((A:1,B:1):1,(C:1,D:1):1);", "((D:1,C:1):1,(B:1,A:1):1);", "((C:1,D:1):1,(A:1,B:1):1);"] {
+        assert_eq!(max_names(newick, TieBreak::Lexicographic, 2), vec!["A", "C"]);
+    }
+    for newick in ["((A:2,B:0):1,C:3);", "(C:3,(B:0,A:2):1);"] {
+        assert_eq!(max_names(newick, TieBreak::Lexicographic, 1), vec!["A"]);
+        assert_eq!(max_names(newick, TieBreak::FewestEdges, 1), vec!["C"]);
+        assert_eq!(max_names(newick, TieBreak::MostEdges, 1), vec!["A"]);
+    }
+    // 0.6 + 0.1 rounds above 0.7 in single precision, which still ties
+    for newick in ["(A:0.7,(B:0.6):0.1,C:0.05);", "((B:0.6):0.1,A:0.7,C:0.05);"] {
+        assert_eq!(max_names(newick, TieBreak::Lexicographic, 1), vec!["A"]);
+        assert_eq!(max_names(newick, TieBreak::MostEdges, 1), vec!["A"]);
+    }
+}
+
+#[test]
+fn pareto_front() {
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,(D:1,F:0):1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let max_fronts = tree_pd.get_pareto_front(Ordering::Greater);
+    let min_fronts = tree_pd.get_pareto_front(Ordering::Less);
+    assert_eq!(max_fronts.len(), 7);
+    for k in 1..=6 {
+        let max_front = &max_fronts[k];
+        assert_eq!(max_front.last().unwrap().pd, tree_pd.get_maxPD(k));
+        assert!(max_front.iter().all(|x| x.taxa.len() == k));
+        assert!(max_front.windows(2).all(|x| x[0].pd < x[1].pd && x[0].num_edges < x[1].num_edges));
+        let best_ratio = max_front.iter().map(|x| x.pd / x.num_edges as f32).fold(f32::MIN, f32::max);
+        assert!(best_ratio >= tree_pd.get_norm_maxPD(k) - 1e-5);
+
+        let min_front = &min_fronts[k];
+        assert_eq!(min_front.last().unwrap().pd, tree_pd.get_minPD(k));
+        assert!(min_front.windows(2).all(|x| x[0].pd > x[1].pd && x[0].num_edges > x[1].num_edges));
+    }
+    // B spans two edges for PD 5, C three edges for PD 7
+    assert_eq!(
+        max_fronts[1].iter().map(|x| (x.pd, x.num_edges, tree.get_node_taxa(x.taxa[0]).unwrap().as_str())).collect_vec(),
+        vec![(5.0, 2, "B"), (7.0, 3, "C")],
+    );
+
+    // multifurcations give the fronts of their resolutions, whose split edges have
+    // length zero
+    let polytomy = PhyloTree::from_newick("((A:1,B:2,G:1.5):3,(C:4,E:0.5,(D:1,F:0):1,H:2):1);".as_bytes()).unwrap();
+    let mut resolved = polytomy.clone();
+    resolve_polytomies(&mut resolved, ResolveStrategy::Balanced);
+    let polytomy_pd = TreePD::new(&polytomy);
+    let resolved_pd = TreePD::new(&resolved);
+    let entries = |tree: &PhyloTree, front: &[ParetoPoint<f32>]| {
+        front
+            .iter()
+            .map(|x| (x.pd, x.num_edges, x.taxa.iter().map(|y| tree.get_node_taxa(*y).unwrap()).sorted().join(",")))
+            .collect_vec()
+    };
+    for op in [Ordering::Greater, Ordering::Less] {
+        let polytomy_fronts = polytomy_pd.get_pareto_front(op);
+        let resolved_fronts = resolved_pd.get_pareto_front(op);
+        for k in 1..=8 {
+            assert_eq!(entries(&polytomy, &polytomy_fronts[k]), entries(&resolved, &resolved_fronts[k]));
+            assert!(polytomy_fronts[k].iter().all(|x| x.taxa.len() == k));
+        }
+        assert_eq!(polytomy_fronts[8].last().unwrap().pd, 17.0);
+    }
+}
+
+#[test]
+fn gen_pd_normalizations() {
+    struct SquaredTaxa;
+    impl Normalization<f32> for SquaredTaxa {
+        fn normalize(&self, summary: &TaxaSetSummary<f32>) -> f32 {
+            summary.pd / (summary.num_taxa * summary.num_taxa) as f32
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let (max_edges, max_edges_set) = tree_pd.get_max_genPD_with_norm(&NormalizationKind::Edges).unwrap();
+    assert!((max_edges - tree_pd.get_max_genPD().unwrap()).abs() < 1e-5);
+    assert_eq!(max_edges_set, tree_pd.get_max_genPD_set().unwrap().collect_vec());
+    let (min_edges, _) = tree_pd.get_min_genPD_with_norm(&NormalizationKind::Edges).unwrap();
+    assert!((min_edges - tree_pd.get_min_genPD().unwrap()).abs() < 1e-5);
+
+    let (max_taxa, _) = tree_pd.get_max_genPD_with_norm(&NormalizationKind::Taxa).unwrap();
+    let expected = (3..=5).map(|k| tree_pd.get_maxPD(k) / k as f32).fold(f32::MIN, f32::max);
+    assert_eq!(max_taxa, expected);
+    assert_eq!(tree_pd.get_max_genPD_with_norm(&NormalizationKind::TreeLength).unwrap().0, 1.0);
+    let (min_clade, min_clade_set) = tree_pd.get_min_genPD_with_norm(&NormalizationKind::MrcaClade).unwrap();
+    assert_eq!(min_clade, 8.0 / 14.5);
+    assert_eq!(tree_pd.get_taxa_names(min_clade_set.into_iter()).unwrap().into_iter().sorted().collect_vec(), vec!["A", "B", "D"]);
+    assert_eq!(tree_pd.get_max_genPD_with_norm(&NormalizationKind::CrownAge).unwrap().0, 14.5 / 7.0);
+    let (max_squared, max_squared_set) = tree_pd.get_max_genPD_with_norm(&SquaredTaxa).unwrap();
+    assert_eq!((max_squared, max_squared_set.len()), (13.0 / 9.0, 3));
+    assert_eq!("tree_length".parse::<NormalizationKind>(), Ok(NormalizationKind::TreeLength));
+
+    let cherry = PhyloTree::from_newick("(A:1,B:2);".as_bytes()).unwrap();
+    assert_eq!(TreePD::new(&cherry).get_max_genPD_with_norm(&NormalizationKind::Taxa), None);
+
+    // the best set may be below the root, and spans fewer taxa than the maxPD sets
+    let tree = PhyloTree::from_newick("(X:10,((A:1,B:1):0.1,(C:1,D:1):0.1):0.1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let (max_crown, max_crown_set) = tree_pd.get_max_genPD_with_norm(&NormalizationKind::CrownAge).unwrap();
+    assert!((max_crown - 4.3 / 1.1).abs() < 1e-5);
+    assert_eq!(tree_pd.get_taxa_names(max_crown_set.into_iter()).unwrap().into_iter().sorted().collect_vec(), vec!["A", "B", "C", "D"]);
+}
+
+#[test]
+fn gen_pd_brute_force() {
+    let gen_pd = |tree_pd: &TreePD<String, f32, f32>, norm: &NormalizationKind, op: Ordering| match op {
+        Ordering::Greater => tree_pd.get_max_genPD_with_norm(norm).map(|x| x.0),
+        _ => tree_pd.get_min_genPD_with_norm(norm).map(|x| x.0),
+    };
+    let close = |x: Option<f32>, y: Option<f32>| match (x, y) {
+        (Some(x), Some(y)) => (x - y).abs() < 1e-4 * y.abs().max(1.0),
+        (x, y) => x == y,
+    };
+    let mut rng = StdRng::seed_from_u64(11);
+    for _ in 0..20 {
+        let taxa = (0..8).collect_vec();
+        let newick = format!("{};", random_newick(&taxa, &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        for op in [Ordering::Greater, Ordering::Less] {
+            for norm in [NormalizationKind::Taxa, NormalizationKind::TreeLength, NormalizationKind::MrcaClade] {
+                let expected = brute_force_gen_pd(&tree, &norm, op);
+                assert!(close(gen_pd(&tree_pd, &norm, op), expected), "{:?} {:?} {}", norm, op, newick);
+            }
+            // normalized PD per edge follows the normalized PD tables
+            let edges = gen_pd(&tree_pd, &NormalizationKind::Edges, op);
+            let expected = match op {
+                Ordering::Greater => tree_pd.get_max_genPD(),
+                _ => tree_pd.get_min_genPD(),
+            };
+            assert!(close(edges, expected), "{:?} {}", op, newick);
+            let brute_force = brute_force_gen_pd(&tree, &NormalizationKind::Edges, op).unwrap();
+            assert!(edges.unwrap().partial_cmp(&brute_force) != Some(op), "{:?} {}", op, newick);
+        }
+
+        // the crown age of a set is that of its most recent common ancestor on ultrametric trees
+        let newick = format!("{};", random_ultrametric_newick(&taxa, 12, &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        for op in [Ordering::Greater, Ordering::Less] {
+            let norm = NormalizationKind::CrownAge;
+            assert!(close(gen_pd(&tree_pd, &norm, op), brute_force_gen_pd(&tree, &norm, op)), "{:?} {}", op, newick);
+        }
+    }
+}
+
+#[test]
+fn max_pd_ranking() {
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..10 {
+        let taxa = (0..12).collect_vec();
+        let newick = format!("{};", random_newick(&taxa, &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let ranking = tree_pd.get_maxPD_ranking();
+        assert_eq!(ranking.iter().map(|x| x.0).sorted().collect_vec(), tree.get_leaf_ids().sorted().collect_vec());
+        let mut max_pd = 0.0;
+        for (k, (_, gain)) in ranking.into_iter().enumerate() {
+            max_pd += gain;
+            assert_eq!(max_pd, tree_pd.get_maxPD(k + 1));
+        }
+    }
+
+    // multifurcations and unary nodes need no resolution
+    let tree = PhyloTree::from_newick("((A:1,B:2,F:2):3,((C:4):2,D:1):1);".as_bytes()).unwrap();
+    let (edge_lengths, _) = tree.resolve_edge_lengths(MissingLengthPolicy::Zero).unwrap();
+    let ranking = tree.compute_maxPD_ranking(&edge_lengths)
+        .into_iter()
+        .map(|(leaf_id, gain)| (tree.get_node_taxa(leaf_id).unwrap().as_str(), gain))
+        .collect_vec();
+    assert_eq!(ranking, vec![("C", 7.0), ("B", 5.0), ("F", 2.0), ("A", 1.0), ("D", 1.0)]);
+}
+
+#[test]
+fn greedy_max_profile() {
+    // maxPD for every number of taxa, summing the gains of the maxPD ranking
+    let profile = |tree: &PhyloTree, edge_lengths: &[f32]| {
+        let mut profile = vec![0.0];
+        for (_, gain) in tree.compute_maxPD_ranking(edge_lengths) {
+            profile.push(profile.last().unwrap() + gain);
+        }
+        profile
+    };
+    let mut rng = StdRng::seed_from_u64(11);
+    for num_taxa in [1, 2, 5, 20, 30] {
+        let taxa = (0..num_taxa).collect_vec();
+        let newick = format!("{};", random_newick(&taxa, &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let (edge_lengths, _) = tree.resolve_edge_lengths(MissingLengthPolicy::Zero).unwrap();
+        let profile = profile(&tree, &edge_lengths);
+        assert_eq!(profile.len(), num_taxa + 1);
+        for (k, max_pd) in profile.into_iter().enumerate() {
+            assert_eq!(max_pd, tree_pd.get_maxPD(k));
+        }
+    }
+
+    // multifurcations and unary nodes need no resolution
+    let tree = PhyloTree::from_newick("((A:1.5,B:2,F:2.25):3,((C:4):2,D:1):1);".as_bytes()).unwrap();
+    let (edge_lengths, _) = tree.resolve_edge_lengths(MissingLengthPolicy::Zero).unwrap();
+    assert_eq!(profile(&tree, &edge_lengths), vec![0.0, 7.0, 12.25, 14.25, 15.75, 16.75]);
+}
+
+#[test]
+fn complement() {
+    let mut rng = StdRng::seed_from_u64(3);
+    for _ in 0..5 {
+        let newick = format!("{};", random_newick(&(0..9).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        let protected = leaf_ids[..3].to_vec();
+        let complements = tree_pd.get_complement(&protected);
+        assert_eq!(complements.len(), 7);
+        for (k, (gain, added)) in complements.into_iter().enumerate() {
+            assert_eq!(added.len(), k);
+            assert!(added.iter().all(|x| !protected.contains(x)));
+            let with_added = protected.iter().chain(added.iter()).copied().collect_vec();
+            assert_eq!(gain, rooted_pd(&tree, &with_added) - rooted_pd(&tree, &protected));
+            let best_gain = leaf_ids[3..]
+                .iter()
+                .copied()
+                .combinations(k)
+                .map(|x| rooted_pd(&tree, &[protected.clone(), x].concat()) - rooted_pd(&tree, &protected))
+                .fold(f32::MIN, f32::max);
+            assert_eq!(gain, best_gain);
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let complements = tree_pd.get_complement_by_names(&["C".to_string(), "A".to_string()]).unwrap();
+    assert_eq!(complements[1], (2.0, vec!["B".to_string()]));
+    assert_eq!(complements[3].0, 3.5);
+    assert!(tree_pd.get_complement_by_names(&["X".to_string()]).is_err());
+}
+
+#[test]
+fn inverse_queries() {
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let names = |taxa: Vec<usize>| taxa.into_iter().map(|x| tree.get_node_taxa(x).unwrap().as_str()).sorted().collect_vec();
+    // maxPD profile: 0, 7, 12, 13, 14, 14.5
+    let (num_taxa, taxa) = tree_pd.get_min_taxa_reaching_PD(12.5).unwrap();
+    assert_eq!((num_taxa, names(taxa)), (3, vec!["A", "B", "C"]));
+    assert_eq!(tree_pd.get_min_taxa_reaching_PD(12.0).unwrap().0, 2);
+    assert_eq!(tree_pd.get_min_taxa_reaching_PD(0.0).unwrap(), (0, vec![]));
+    assert_eq!(tree_pd.get_min_taxa_reaching_PD(0.5 * tree_pd.get_maxPD(5)).unwrap().0, 2);
+    assert_eq!(tree_pd.get_min_taxa_reaching_PD(15.0), None);
+    // minPD profile: 0, 2, 4.5, 8, ...
+    let (num_taxa, taxa) = tree_pd.get_max_taxa_within_PD(5.0).unwrap();
+    assert_eq!((num_taxa, names(taxa)), (2, vec!["D", "E"]));
+    assert_eq!(tree_pd.get_max_taxa_within_PD(8.0).unwrap().0, 3);
+    assert_eq!(tree_pd.get_max_taxa_within_PD(100.0).unwrap().0, 5);
+    assert_eq!(tree_pd.get_max_taxa_within_PD(-1.0), None);
+    for k in 0..=5 {
+        let target = tree_pd.get_maxPD(k);
+        assert!(tree_pd.get_min_taxa_reaching_PD(target).unwrap().0 <= k);
+        assert!(tree_pd.get_max_taxa_within_PD(tree_pd.get_minPD(k)).unwrap().0 >= k);
+    }
+
+    // node costs make maxPD non-monotone in k
+    let newick = "(A:5,(B:1[&weight=10],(C:1[&weight=10],(D:1[&weight=10],E:1[&weight=10]):0):0):0);";
+    let tree = from_annotated_newick(newick.as_bytes(), Some("weight")).unwrap();
+    let options = PDOptions { node_weights: NodeWeightPolicy::Cost, ..Default::default() };
+    let tree_pd = TreePD::with_options(&tree, options).unwrap();
+    assert_eq!((0..=3).map(|k| tree_pd.get_maxPD(k)).collect_vec(), vec![0.0, 5.0, -4.0, -13.0]);
+    assert_eq!(tree_pd.get_min_taxa_reaching_PD(3.0).unwrap().0, 1);
+    assert_eq!(tree_pd.get_max_taxa_within_PD(-4.0).unwrap().0, tree_pd.get_tree().num_taxa());
+    let sites = Sites::new(&tree_pd, &[(vec!["A".to_string()], 1.0)]);
+    assert_eq!(sites, Err(PDError::NegativeEdgeLengths { num_negative: 4 }));
+    let projects = [("A".to_string(), Project { cost: 1, survival: 0.5 })];
+    assert_eq!(solve_noahs_ark(&tree_pd, &projects, 1).err(), Some(PDError::NegativeEdgeLengths { num_negative: 4 }));
+}
+
+#[test]
+fn robust_max_pd() {
+    fn worst_case_pd(tree: &PhyloTree, taxa: &[usize], removals: usize) -> f32 {
+        taxa.iter()
+            .copied()
+            .combinations(taxa.len() - removals)
+            .map(|x| rooted_pd(tree, &x))
+            .fold(f32::MAX, f32::min)
+    }
+
+    let mut rng = StdRng::seed_from_u64(5);
+    for _ in 0..4 {
+        let newick = format!("{};", random_newick(&(0..8).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        for removals in 0..=3 {
+            for num_taxa in removals..=6 {
+                let (value, taxa) = tree_pd.get_robust_maxPD(num_taxa, removals);
+                assert_eq!(taxa.len(), num_taxa);
+                assert_eq!(value, worst_case_pd(&tree, &taxa, removals));
+                let best = leaf_ids
+                    .iter()
+                    .copied()
+                    .combinations(num_taxa)
+                    .map(|x| worst_case_pd(&tree, &x, removals))
+                    .fold(f32::MIN, f32::max);
+                match removals <= ROBUST_EXACT_MAX_REMOVALS {
+                    true => assert_eq!(value, best),
+                    false => assert!(value <= best),
+                }
+            }
+        }
+        assert_eq!(tree_pd.get_robust_maxPD(5, 0).0, tree_pd.get_maxPD(5));
+    }
+}
+
+#[test]
+fn valued_max_pd() {
+    let mut rng = StdRng::seed_from_u64(5);
+    for _ in 0..5 {
+        let newick = format!("{};", random_newick(&(0..8).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        let values: std::collections::HashMap<String, f32> = leaf_ids
+            .iter()
+            .map(|x| (tree.get_node_taxa(*x).unwrap().clone(), rng.gen_range(-2..6) as f32))
+            .collect();
+        let value = |taxa: &[usize]| taxa.iter().map(|x| values[tree.get_node_taxa(*x).unwrap()]).sum::<f32>();
+        for lambda in [0.0, 0.5, 2.0] {
+            let selections = tree_pd.get_valued_maxPD(&values, lambda).unwrap();
+            assert_eq!(selections.len(), 9);
+            for (k, selection) in selections.into_iter().enumerate() {
+                assert_eq!(selection.taxa.len(), k);
+                assert_eq!(selection.pd, rooted_pd(&tree, &selection.taxa));
+                assert_eq!(selection.value, value(&selection.taxa));
+                assert_eq!(selection.objective, selection.pd + lambda * selection.value);
+                let best = leaf_ids
+                    .iter()
+                    .copied()
+                    .combinations(k)
+                    .map(|x| rooted_pd(&tree, &x) + lambda * value(&x))
+                    .fold(f32::MIN, f32::max);
+                assert_eq!(selection.objective, best);
+            }
+        }
+        assert_eq!(
+            tree_pd.get_valued_maxPD(&values, 0.0).unwrap()[3].pd,
+            tree_pd.get_maxPD(3)
+        );
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let values = [("D".to_string(), 6.0)].into_iter().collect();
+    let sweep = tree_pd.get_valued_maxPD_sweep(&values, 1, 0.0, 1.0).unwrap();
+    let names = sweep.iter().map(|x| tree_pd.get_taxa_names(x.selection.taxa.iter().copied()).unwrap()).collect_vec();
+    assert_eq!(names, vec![vec!["C".to_string()], vec!["D".to_string()]]);
+    // C has PD 7 and D has PD 2 plus 6 lambda, which cross at lambda 5/6
+    assert_eq!((sweep[0].lambda_min, sweep[0].lambda_max), (0.0, 5.0 / 6.0));
+    assert_eq!((sweep[1].lambda_min, sweep[1].lambda_max), (5.0 / 6.0, 1.0));
+    assert_eq!((sweep[1].selection.pd, sweep[1].selection.value), (2.0, 6.0));
+
+    // the sweep agrees with the DP on a fine grid of lambda
+    let mut rng = StdRng::seed_from_u64(6);
+    for _ in 0..5 {
+        let newick = format!("{};", random_newick(&(0..10).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let values: std::collections::HashMap<String, f32> = tree
+            .get_leaf_ids()
+            .map(|x| (tree.get_node_taxa(x).unwrap().clone(), rng.gen_range(0..8) as f32))
+            .collect();
+        let sweep = tree_pd.get_valued_maxPD_sweep(&values, 4, -1.0, 3.0).unwrap();
+        assert_eq!(sweep.first().unwrap().lambda_min, -1.0);
+        assert_eq!(sweep.last().unwrap().lambda_max, 3.0);
+        for (x, y) in sweep.iter().tuple_windows() {
+            assert_eq!(x.lambda_max, y.lambda_min);
+            assert!(x.selection.value < y.selection.value);
+        }
+        for i in 0..=40 {
+            let lambda = -1.0 + i as f32 / 10.0;
+            let expected = tree_pd.get_valued_maxPD(&values, lambda).unwrap()[4].objective;
+            let interval = sweep.iter().find(|x| x.lambda_max >= lambda).unwrap();
+            let objective = interval.selection.pd + lambda * interval.selection.value;
+            assert!((objective - expected).abs() < 1e-3, "{} {}", lambda, newick);
+        }
+    }
+    let unknown = [("X".to_string(), 1.0)].into_iter().collect();
+    assert!(tree_pd.get_valued_maxPD(&unknown, 1.0).is_err());
+}
+
+#[test]
+fn node_weights() {
+    let newick = "((A[&weight=5]:1,B:2)[&support=0.9,weight=2]:3,((C:4,E:0.5):2,D:1[&weight=10]):1);";
+    let tree = from_annotated_newick(newick.as_bytes(), Some("weight")).unwrap();
+    let node_id = |taxon: &str| tree.get_node_ids().find(|x| tree.get_node_taxa(*x).map(|x| x.as_str()) == Some(taxon)).unwrap();
+    assert_eq!(tree.get_node(node_id("A")).unwrap().get_zeta(), Some(5.0));
+    assert_eq!(tree.get_node(node_id("D")).unwrap().get_zeta(), Some(10.0));
+    assert_eq!(tree.get_node(node_id("D")).unwrap().get_weight(), Some(1.0));
+    let ab_id = tree.get_node_parent_id(node_id("A")).unwrap();
+    assert_eq!((tree.get_node_taxa(ab_id), tree.get_node(ab_id).unwrap().get_zeta()), (None, Some(2.0)));
+    assert_eq!(tree.get_leaves().len(), 5);
+
+    let max_set = |node_weights| {
+        let tree_pd = TreePD::with_options(&tree, PDOptions { node_weights, ..Default::default() }).unwrap();
+        (tree_pd.get_maxPD(1), tree_pd.get_maxPD_taxa_names(1).unwrap())
+    };
+    assert_eq!(max_set(NodeWeightPolicy::Ignore), (7.0, vec!["C".to_string()]));
+    assert_eq!(max_set(NodeWeightPolicy::Bonus), (12.0, vec!["D".to_string()]));
+    assert_eq!(max_set(NodeWeightPolicy::Cost), (7.0, vec!["C".to_string()]));
+    assert!(from_annotated_newick("(A[&weight=x]:1,B:1);".as_bytes(), Some("weight")).is_err());
+    // bare numbers (such as support values) are only weights when no key is requested,
+    // and labels keep their braces and quoted brackets
+    let newick = "((A{1}:1,'x[1]':2)[90]:3,B[&weight=4]:1);";
+    let zetas = |key| {
+        let tree = from_annotated_newick(newick.as_bytes(), key).unwrap();
+        tree.get_node_ids()
+            .map(|x| (tree.get_node_taxa(x).cloned().unwrap_or_default(), tree.get_node(x).unwrap().get_zeta()))
+            .filter(|(label, zeta)| !label.is_empty() || zeta.is_some())
+            .sorted_by(|x, y| x.0.cmp(&y.0))
+            .collect_vec()
+    };
+    let labelled = |b: Option<f32>| vec![("'x[1]'".to_string(), None), ("A{1}".to_string(), None), ("B".to_string(), b)];
+    assert_eq!(zetas(Some("weight")), labelled(Some(4.0)));
+    assert_eq!(zetas(None), [vec![(String::new(), Some(90.0))], labelled(None)].concat());
+
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..5 {
+        let newick = format!("{};", random_newick(&(0..8).collect_vec(), &mut rng));
+        let mut tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        for node_id in tree.get_node_ids().collect_vec() {
+            tree.get_node_mut(node_id).unwrap().set_zeta(Some(rng.gen_range(0..4) as f32));
+        }
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        for (node_weights, sign) in [(NodeWeightPolicy::Bonus, 1.0), (NodeWeightPolicy::Cost, -1.0)] {
+            let tree_pd = TreePD::with_options(&tree, PDOptions { node_weights, ..Default::default() }).unwrap();
+            let weighted_pd = |taxa: &[usize]| {
+                let spanned = taxa
+                    .iter()
+                    .flat_map(|x| std::iter::successors(Some(*x), |y| tree.get_node_parent_id(*y)))
+                    .filter(|x| *x != tree.get_root_id())
+                    .unique();
+                rooted_pd(&tree, taxa) + sign * spanned.map(|x| tree.get_node(x).unwrap().get_zeta().unwrap()).sum::<f32>()
+            };
+            for k in 1..leaf_ids.len() + 1 {
+                let values = leaf_ids.iter().copied().combinations(k).map(|x| weighted_pd(&x)).collect_vec();
+                assert_eq!(tree_pd.get_maxPD(k), values.iter().copied().fold(f32::MIN, f32::max));
+                assert_eq!(tree_pd.get_minPD(k), values.iter().copied().fold(f32::MAX, f32::min));
+            }
+        }
+    }
+}
+
+#[test]
+fn viable_max_pd() {
+    let mut rng = StdRng::seed_from_u64(11);
+    for _ in 0..5 {
+        let newick = format!("{};", random_newick(&(0..9).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let names = (0..9).map(|x| format!("T{}", x)).collect_vec();
+        // T0 and T1 have no dependencies, other taxa depend on lower-numbered ones,
+        // and T7 and T8 depend on each other
+        let mut edges = (2..7)
+            .flat_map(|x| (0..x).map(move |y| (x, y)))
+            .filter(|_| rng.gen_bool(0.3))
+            .map(|(x, y)| (names[x].clone(), names[y].clone()))
+            .collect_vec();
+        edges.push((names[6].clone(), names[5].clone()));
+        edges.push((names[7].clone(), names[8].clone()));
+        edges.push((names[8].clone(), names[7].clone()));
+        let network = DependencyNetwork::from_edges(&tree_pd, &edges).unwrap();
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        for k in 0..leaf_ids.len() + 1 {
+            let best = leaf_ids
+                .iter()
+                .copied()
+                .combinations(k)
+                .filter(|x| network.is_viable(x))
+                .map(|x| rooted_pd(&tree, &x))
+                .fold(None, |acc: Option<f32>, x| Some(acc.map_or(x, |y| y.max(x))));
+            let selection = network.get_viable_maxPD(&tree_pd, k);
+            assert_eq!(selection.as_ref().map(|x| x.pd), best);
+            if let Some(selection) = selection {
+                assert!(selection.exact);
+                assert_eq!(selection.taxa.len(), k);
+                assert!(network.is_viable(&selection.taxa));
+                assert_eq!(selection.pd, rooted_pd(&tree, &selection.taxa));
+            }
+            if let Some((pd, taxa)) = network.get_greedy_viable_maxPD(&tree_pd, k) {
+                assert!(network.is_viable(&taxa));
+                assert_eq!(pd, rooted_pd(&tree, &taxa));
+            }
+            if let Some(selection) = network.get_viable_maxPD_with_limit(&tree_pd, k, 1) {
+                assert!(network.is_viable(&selection.taxa));
+            }
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let edge = |x: &str, y: &str| (x.to_string(), y.to_string());
+    let network = DependencyNetwork::from_edges(&tree_pd, &[edge("C", "D"), edge("B", "A")]).unwrap();
+    let selection = network.get_viable_maxPD(&tree_pd, 2).unwrap();
+    assert_eq!(tree_pd.get_taxa_names(selection.taxa.into_iter()).unwrap().into_iter().sorted().collect_vec(), vec!["C", "D"]);
+    assert_eq!(selection.pd, 8.0);
+    assert!(DependencyNetwork::from_edges(&tree_pd, &[edge("C", "X")]).is_err());
+}
+
+#[test]
+fn noahs_ark() {
+    let mut rng = StdRng::seed_from_u64(13);
+    for _ in 0..5 {
+        let newick = format!("{};", random_newick(&(0..6).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let num_projects = (0..6).map(|_| rng.gen_range(0..3)).collect_vec();
+        let projects = (0..6)
+            .flat_map(|x| std::iter::repeat_n(x, num_projects[x]))
+            .map(|x| {
+                let project = Project { cost: rng.gen_range(0..4), survival: [0.2, 0.5, 0.8, 1.0][rng.gen_range(0..4)] };
+                (format!("T{}", x), project)
+            })
+            .collect_vec();
+        let expected_pd = |rows: &[usize]| {
+            let mut lost = vec![1.0_f32; tree.get_node_ids().max().unwrap() + 1];
+            for row in rows {
+                let leaf_id = tree.get_node_ids().find(|x| tree.get_node_taxa(*x) == Some(&projects[*row].0)).unwrap();
+                for node_id in std::iter::successors(Some(leaf_id), |x| tree.get_node_parent_id(*x)) {
+                    lost[node_id] *= 1.0 - projects[*row].1.survival;
+                }
+            }
+            tree.get_node_ids()
+                .filter(|x| *x != tree.get_root_id())
+                .map(|x| tree.get_node(x).unwrap().get_weight().unwrap() * (1.0 - lost[x]))
+                .sum::<f32>()
+        };
+        for budget in [0, 2, 5] {
+            let solution = solve_noahs_ark(&tree_pd, &projects, budget).unwrap();
+            assert!(solution.projects.iter().map(|x| &projects[*x].0).all_unique());
+            assert_eq!(solution.cost, solution.projects.iter().map(|x| projects[*x].1.cost).sum::<usize>());
+            assert!(solution.cost <= budget);
+            assert!((solution.expected_pd - expected_pd(&solution.projects)).abs() < 1e-4);
+            // every choice of at most one project per taxon
+            let best = (0..6)
+                .map(|x| {
+                    let rows = (0..projects.len()).filter(|y| projects[*y].0 == format!("T{}", x)).map(Some);
+                    std::iter::once(None).chain(rows).collect_vec()
+                })
+                .multi_cartesian_product()
+                .map(|x| x.into_iter().flatten().collect_vec())
+                .filter(|x| x.iter().map(|y| projects[*y].1.cost).sum::<usize>() <= budget)
+                .map(|x| expected_pd(&x))
+                .fold(0.0, f32::max);
+            assert!((solution.expected_pd - best).abs() < 1e-4);
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let project = |taxon: &str, cost, survival| (taxon.to_string(), Project { cost, survival });
+    let projects = [project("A", 1, 0.5), project("C", 2, 1.0), project("B", 0, 0.5)];
+    let solution = solve_noahs_ark(&tree_pd, &projects, 0).unwrap();
+    assert_eq!((solution.expected_pd, solution.projects), (2.5, vec![2]));
+    let solution = solve_noahs_ark(&tree_pd, &projects, 3).unwrap();
+    assert_eq!((solution.cost, solution.projects), (3, vec![0, 1, 2]));
+    assert!(solve_noahs_ark(&tree_pd, &[project("A", 1, 1.5)], 1).is_err());
+}
+
+#[test]
+fn reserve_selection() {
+    let mut rng = StdRng::seed_from_u64(17);
+    for _ in 0..5 {
+        let newick = format!("{};", random_newick(&(0..10).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let site_rows = (0..7)
+            .map(|_| {
+                let taxa = (0..10).filter(|_| rng.gen_bool(0.25)).map(|x| format!("T{}", x)).collect_vec();
+                (taxa, rng.gen_range(1..4) as f32)
+            })
+            .collect_vec();
+        let union_pd = |sites: &[usize]| {
+            let taxa = sites
+                .iter()
+                .flat_map(|x| site_rows[*x].0.iter())
+                .map(|taxon| tree.get_node_ids().find(|x| tree.get_node_taxa(*x) == Some(taxon)).unwrap())
+                .unique()
+                .collect_vec();
+            rooted_pd(&tree, &taxa)
+        };
+        for unit_costs in [true, false] {
+            let rows = site_rows.iter().map(|(taxa, cost)| (taxa.clone(), if unit_costs { 1.0 } else { *cost })).collect_vec();
+            let sites = Sites::new(&tree_pd, &rows).unwrap();
+            for budget in [1.0, 2.0, 4.0] {
+                let best = (0..7)
+                    .powerset()
+                    .filter(|x| x.iter().map(|y| rows[*y].1).sum::<f32>() <= budget)
+                    .map(|x| union_pd(&x))
+                    .fold(0.0, f32::max);
+                let greedy = sites.get_greedy_reserves(&tree_pd, budget);
+                let local = sites.get_local_search_reserves(&tree_pd, budget);
+                let exact = sites.get_exact_reserves(&tree_pd, budget, 100_000);
+                for selection in [&greedy, &local, &exact] {
+                    assert!(selection.cost <= budget);
+                    assert_eq!(selection.pd, union_pd(&selection.sites));
+                }
+                if unit_costs {
+                    assert!(greedy.pd >= (1.0 - 1.0 / std::f32::consts::E) * best);
+                }
+                assert!(local.pd >= greedy.pd);
+                assert!(exact.exact);
+                assert_eq!(exact.pd, best);
+            }
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let site = |taxa: &[&str], cost| (taxa.iter().map(|x| x.to_string()).collect_vec(), cost);
+    let sites = Sites::new(&tree_pd, &[site(&["A", "C"], 1.0), site(&["B", "E"], 1.0), site(&["D"], 1.0)]).unwrap();
+    let selection = sites.get_exact_reserves(&tree_pd, 2.0, 100_000);
+    assert_eq!((selection.pd, selection.sites), (13.5, vec![0, 1]));
+    assert_eq!(sites.get_sites_PD(&tree_pd, &[2]), 2.0);
+    assert!(Sites::new(&tree_pd, &[site(&["X"], 1.0)]).is_err());
+}
+
+#[test]
+fn conflict_free_pd() {
+    let mut rng = StdRng::seed_from_u64(19);
+    for _ in 0..5 {
+        let newick = format!("{};", random_newick(&(0..9).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let edges = (0..9)
+            .tuple_combinations()
+            .filter(|_| rng.gen_bool(0.25))
+            .map(|(x, y)| (format!("T{}", x), format!("T{}", y)))
+            .collect_vec();
+        let graph = ConflictGraph::from_edges(&tree_pd, &edges).unwrap();
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        for op in [Ordering::Greater, Ordering::Less] {
+            for k in 1..leaf_ids.len() + 1 {
+                let pds = leaf_ids
+                    .iter()
+                    .copied()
+                    .combinations(k)
+                    .filter(|x| graph.is_conflict_free(x))
+                    .map(|x| rooted_pd(&tree, &x))
+                    .collect_vec();
+                let best = match op {
+                    Ordering::Greater => pds.iter().copied().reduce(f32::max),
+                    _ => pds.iter().copied().reduce(f32::min),
+                };
+                let selection = graph.get_conflict_free_PD(&tree_pd, op, k, std::time::Duration::from_secs(60));
+                assert_eq!(selection.as_ref().map(|x| x.pd), best);
+                if let Some(selection) = selection {
+                    assert!(selection.exact);
+                    assert_eq!(selection.taxa.len(), k);
+                    assert!(graph.is_conflict_free(&selection.taxa));
+                    assert_eq!(selection.pd, rooted_pd(&tree, &selection.taxa));
+                }
+                if let Some(selection) = graph.get_conflict_free_PD(&tree_pd, op, k, std::time::Duration::ZERO) {
+                    assert!(graph.is_conflict_free(&selection.taxa));
+                    assert_eq!(selection.pd, rooted_pd(&tree, &selection.taxa));
+                }
+            }
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let graph = ConflictGraph::from_edges(&tree_pd, &[("B".to_string(), "C".to_string())]).unwrap();
+    let selection = graph.get_conflict_free_PD(&tree_pd, Ordering::Greater, 2, std::time::Duration::from_secs(60)).unwrap();
+    // B and C would give the unconstrained maxPD of 12
+    assert_eq!(tree_pd.get_maxPD(2), 12.0);
+    assert_eq!((selection.pd, tree_pd.get_taxa_names(selection.taxa.into_iter()).unwrap().into_iter().sorted().collect_vec()), (11.0, vec!["A".to_string(), "C".to_string()]));
+}
+
+#[test]
+fn group_quotas() {
+    let mut rng = StdRng::seed_from_u64(23);
+    for round in 0..10 {
+        let newick = format!("{};", random_newick(&(0..9).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        let names = |taxa: Vec<usize>| taxa.into_iter().map(|x| tree.get_node_taxa(x).unwrap().clone()).collect_vec();
+        // clades of the tree in even rounds, arbitrary groups in odd rounds
+        let group_taxa = match round % 2 {
+            0 => tree
+                .get_node_ids()
+                .filter(|_| rng.gen_bool(0.4))
+                .map(|x| names(tree.get_cluster_ids(x).collect_vec()))
+                .collect_vec(),
+            _ => (0..3)
+                .map(|_| names(leaf_ids.iter().copied().filter(|_| rng.gen_bool(0.4)).collect_vec()))
+                .collect_vec(),
+        };
+        let group_rows = group_taxa
+            .into_iter()
+            .map(|taxa| {
+                let quota = GroupQuota { min: rng.gen_range(0..2), max: rng.gen_range(1..4) };
+                (taxa, quota)
+            })
+            .collect_vec();
+        let groups = Groups::new(&tree_pd, &group_rows).unwrap();
+        for op in [Ordering::Greater, Ordering::Less] {
+            for k in 1..leaf_ids.len() + 1 {
+                let pds = leaf_ids
+                    .iter()
+                    .copied()
+                    .combinations(k)
+                    .filter(|x| groups.is_feasible(x))
+                    .map(|x| rooted_pd(&tree, &x))
+                    .collect_vec();
+                let best = match op {
+                    Ordering::Greater => pds.iter().copied().reduce(f32::max),
+                    _ => pds.iter().copied().reduce(f32::min),
+                };
+                let selection = groups.get_quota_PD(&tree_pd, op, k, std::time::Duration::from_secs(60));
+                assert_eq!(selection.as_ref().map(|x| x.pd), best);
+                if let Some(selection) = selection {
+                    assert!(selection.exact);
+                    assert_eq!(selection.taxa.len(), k);
+                    assert!(groups.is_feasible(&selection.taxa));
+                    assert_eq!(selection.pd, rooted_pd(&tree, &selection.taxa));
+                }
+            }
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    let group = |taxa: &[&str], min, max| (taxa.iter().map(|x| x.to_string()).collect_vec(), GroupQuota { min, max });
+    // maxPD of 3 taxa is 13, with A, B and C or with B, C and D, but at most one of A and B is allowed
+    let groups = Groups::new(&tree_pd, &[group(&["A", "B"], 0, 1), group(&["C", "D", "E"], 0, 3)]).unwrap();
+    let selection = groups.get_quota_PD(&tree_pd, Ordering::Greater, 3, std::time::Duration::from_secs(60)).unwrap();
+    assert_eq!(selection.pd, 13.0);
+    assert_eq!(tree_pd.get_taxa_names(selection.taxa.into_iter()).unwrap().into_iter().sorted().collect_vec(), vec!["B", "C", "D"]);
+    let groups = Groups::new(&tree_pd, &[group(&["A", "D"], 2, 2)]).unwrap();
+    let selection = groups.get_quota_PD(&tree_pd, Ordering::Greater, 2, std::time::Duration::from_secs(60)).unwrap();
+    assert_eq!((selection.pd, selection.exact), (6.0, true));
+    assert!(Groups::new(&tree_pd, &[group(&["X"], 0, 1)]).is_err());
+}
+
+#[test]
+fn stratified_max_pd() {
+    let mut rng = StdRng::seed_from_u64(29);
+    for _ in 0..10 {
+        let newick = format!("{};", random_newick(&(0..9).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        let depth = rng.gen_range(1..6) as f32;
+        let clade_ids = tree_pd.get_depth_cut(depth);
+        // clades at a cut are disjoint and hold every leaf at or past the cut
+        let clades = clade_ids.iter().map(|x| tree.get_cluster_ids(*x).collect_vec()).collect_vec();
+        let leaf_depth = |x: usize| rooted_pd(&tree, &[x]);
+        assert_eq!(clades.iter().map(|x| x.len()).sum::<usize>(), leaf_ids.iter().filter(|x| leaf_depth(**x) >= depth).count());
+        assert!(clades.concat().into_iter().all(|x| leaf_depth(x) >= depth));
+        for min_per_clade in [1, 2] {
+            for k in 0..leaf_ids.len() + 1 {
+                let best = leaf_ids
+                    .iter()
+                    .copied()
+                    .combinations(k)
+                    .filter(|x| clades.iter().all(|clade| x.iter().filter(|y| clade.contains(y)).count() >= min_per_clade.min(clade.len())))
+                    .map(|x| rooted_pd(&tree, &x))
+                    .reduce(f32::max);
+                let selection = tree_pd.get_stratified_maxPD(k, &clade_ids, min_per_clade);
+                assert_eq!(selection.as_ref().map(|x| x.0), best);
+                if let Some((pd, taxa)) = selection {
+                    assert_eq!(taxa.len(), k);
+                    assert_eq!(pd, rooted_pd(&tree, &taxa));
+                }
+            }
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    // cutting at depth 4 leaves A, B and C in clades of their own
+    let clade_ids = tree_pd.get_depth_cut(4.0);
+    assert_eq!(tree_pd.get_taxa_names(clade_ids.iter().copied()).unwrap().into_iter().sorted().collect_vec(), vec!["A", "B", "C"]);
+    assert_eq!(tree_pd.get_stratified_maxPD(2, &clade_ids, 1), None);
+    let (pd, taxa) = tree_pd.get_stratified_maxPD(3, &clade_ids, 1).unwrap();
+    assert_eq!(pd, 13.0);
+    assert_eq!(tree_pd.get_taxa_names(taxa.into_iter()).unwrap().into_iter().sorted().collect_vec(), vec!["A", "B", "C"]);
+    // maxPD of 2 taxa is 12 with B and C, but one of A and B and the taxon D are required
+    let clade_ids = vec![
+        tree_pd.get_clade_id(&["A".to_string(), "B".to_string()]).unwrap(),
+        tree_pd.get_clade_id(&["D".to_string()]).unwrap(),
+    ];
+    let (pd, taxa) = tree_pd.get_stratified_maxPD(2, &clade_ids, 1).unwrap();
+    assert_eq!(pd, 7.0);
+    assert_eq!(tree_pd.get_taxa_names(taxa.into_iter()).unwrap().into_iter().sorted().collect_vec(), vec!["B", "D"]);
+    assert_eq!(tree_pd.get_depth_cut(0.0), vec![tree.get_root_id()]);
+
+    // node weights change PD but not the depth of the cut
+    let newick = "((A:1,B:2)[&weight=5]:3,((C:4,E:0.5):2,D:1):1);";
+    let weighted = from_annotated_newick(newick.as_bytes(), Some("weight")).unwrap();
+    let options = PDOptions { node_weights: NodeWeightPolicy::Bonus, ..Default::default() };
+    let weighted_pd = TreePD::with_options(&weighted, options).unwrap();
+    let clade_ids = weighted_pd.get_depth_cut(4.0);
+    assert_eq!(weighted_pd.get_taxa_names(clade_ids.iter().copied()).unwrap().into_iter().sorted().collect_vec(), vec!["A", "B", "C"]);
+}
+
+#[test]
+fn null_norm_pd() {
+    let mut rng = StdRng::seed_from_u64(31);
+    let kinds = [
+        NormalizationKind::Edges,
+        NormalizationKind::Taxa,
+        NormalizationKind::TreeLength,
+        NormalizationKind::MrcaClade,
+        NormalizationKind::CrownAge,
+    ];
+    for _ in 0..4 {
+        let newick = format!("{};", random_newick(&(0..8).collect_vec(), &mut rng));
+        let tree = PhyloTree::from_newick(newick.as_bytes()).unwrap();
+        let tree_pd = TreePD::new(&tree);
+        let leaf_ids = tree.get_leaf_ids().collect_vec();
+        let weight = |x: usize| tree.get_node(x).unwrap().get_weight().unwrap_or(0.0);
+        let tree_length = tree.get_node_ids().map(weight).sum::<f32>();
+        let summary = |taxa: &[usize]| {
+            let names = taxa.iter().map(|x| tree.get_node_taxa(*x).unwrap().clone()).collect_vec();
+            let mrca_id = tree_pd.get_clade_id(&names).unwrap();
+            let path = |x: usize| std::iter::successors(Some(x), |y| tree.get_node_parent_id(*y)).collect_vec();
+            TaxaSetSummary {
+                pd: rooted_pd(&tree, taxa),
+                num_taxa: taxa.len(),
+                num_edges: taxa
+                    .iter()
+                    .flat_map(|x| path(*x))
+                    .unique()
+                    .filter(|x| tree.get_node_parent_id(*x).is_some() && weight(*x) > 0.0)
+                    .count() as u32,
+                mrca_clade_pd: rooted_pd(&tree, &tree.get_cluster_ids(mrca_id).collect_vec()),
+                tree_length,
+                crown_age: taxa
+                    .iter()
+                    .map(|x| path(*x).into_iter().take_while(|y| *y != mrca_id).map(weight).sum::<f32>())
+                    .fold(0.0, f32::max),
+            }
+        };
+        for kind in kinds {
+            for k in 1..leaf_ids.len() + 1 {
+                let values = leaf_ids
+                    .iter()
+                    .copied()
+                    .combinations(k)
+                    .map(|x| kind.normalize(&summary(&x)) as f64)
+                    .filter(|x| x.is_finite())
+                    .collect_vec();
+                let null = get_null_normPD(&tree_pd, kind, k, 1000, 7);
+                if values.is_empty() {
+                    assert_eq!(null, None);
+                    continue;
+                }
+                let null = null.unwrap();
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                let sd = (values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt();
+                match kind {
+                    NormalizationKind::MrcaClade | NormalizationKind::CrownAge => {
+                        // Monte Carlo estimates lie within a few standard errors
+                        assert!(!null.exact);
+                        let tolerance = 5.0 * sd / (null.samples as f64).sqrt() + 1e-4;
+                        assert!((null.mean as f64 - mean).abs() <= tolerance);
+                    },
+                    _ => {
+                        assert!(null.exact);
+                        assert!((null.mean as f64 - mean).abs() <= 1e-4 * mean.abs().max(1.0));
+                        assert!((null.sd as f64 - sd).abs() <= 1e-3 * sd.max(1.0));
+                    },
+                }
+            }
+        }
+    }
+
+    let tree = PhyloTree::from_newick("((A:1,B:2):3,((C:4,E:0.5):2,D:1):1);".as_bytes()).unwrap();
+    let tree_pd = TreePD::new(&tree);
+    // single taxa have PD 4, 5, 7, 3.5 and 2, spanning 2, 2, 3, 3 and 2 edges
+    let null = get_null_normPD(&tree_pd, NormalizationKind::Taxa, 1, NULL_SAMPLES, 0).unwrap();
+    assert_eq!((null.mean, null.exact), (4.3, true));
+    let null = get_null_normPD(&tree_pd, NormalizationKind::Edges, 1, NULL_SAMPLES, 0).unwrap();
+    assert!((null.mean - (2.0 + 2.5 + 7.0 / 3.0 + 3.5 / 3.0 + 1.0) / 5.0).abs() < 1e-6);
+    assert_eq!(get_null_normPD(&tree_pd, NormalizationKind::Edges, 6, NULL_SAMPLES, 0), None);
+    assert_eq!(get_null_normPD(&tree_pd, NormalizationKind::Edges, 0, NULL_SAMPLES, 0), None);
+    // the profile over all sizes agrees with the distributions of each size
+    for kind in kinds {
+        let profile = get_null_normPD_profile(&tree_pd, kind, 1000, 7);
+        assert_eq!(profile.len(), 6);
+        for (k, null) in profile.into_iter().enumerate() {
+            assert_eq!(null, get_null_normPD(&tree_pd, kind, k, 1000, 7));
+        }
+    }
+}